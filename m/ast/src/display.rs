@@ -60,7 +60,7 @@ impl<'i> fmt::Display for Dereference<'i> {
 
 impl<'i> fmt::Display for Invocation<'i> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self((comment, target, cwd, inp, out, env, args)) = self;
+        let Self((comment, target, cwd, inp, out, env, args, _)) = self;
         for line in comment {
             writeln!(f, "{line}")?;
         }
@@ -8,6 +8,7 @@ pub fn invoc(name: &str) -> Invocation {
         <_>::default(),
         <_>::default(),
         <_>::default(),
+        <_>::default(),
     ))
 }
 
@@ -30,7 +30,10 @@ macro_rules! name {
 
 name![Module, Block];
 
-either![Item, Expr, LetStmt, DefStmt, SrcStmt, Include, IncludeStr, Empty];
+either![
+    Item, Expr, LetStmt, ValStmt, DefStmt, PubDefStmt, ExternStmt, SrcStmt, Include, IncludeStr,
+    Empty
+];
 either![Expr, Invocation, String, Natural, Slice, Variable, Array];
 either![Body, Block];
 either![
@@ -72,8 +75,11 @@ name![Include, Path];
 name![IncludeStr, Ident, Path];
 name![Block, AnyItem, Expr];
 name![LetStmt, Ident, Expr];
+name![ValStmt, Ident, Expr];
 name![SrcStmt, Ident, Expr];
 name![DefStmt, Ident, Body];
+name![PubDefStmt, DefStmt];
+name![ExternStmt, Ident];
 name![RedirectInput, Redirect];
 name![RedirectOutput, Redirect];
 name![String, Text];
@@ -100,7 +106,8 @@ name![
     AnyInvocationInputRedirection,
     AnyInvocationOutputRedirection,
     AnyInvocationEnv,
-    AnyInvocationArg
+    AnyInvocationArg,
+    Span
 ];
 
 pub type InvocationInputRedirection<'i> = RedirectInput<'i>;
@@ -128,3 +135,11 @@ pub type Any<T> = Vec<T>;
 
 pub type Empty<'i> = std::marker::PhantomData<&'i ()>;
 pub type Tupl2<T> = (T, T);
+/// Byte offsets in the source text, end excluded. Empty for synthesized
+/// nodes.
+///
+/// Only invocations have a span: they are what runs and fails, and the
+/// other instructions are located by the invocation before them. The
+/// lifetime is only there for the `name!` macro.
+pub type Span<'i> = Tupl2<Location>;
+pub type Location = usize;
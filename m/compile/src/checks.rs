@@ -0,0 +1,224 @@
+//! Checks of how defs and values are used, against what is known of them
+//! at compile time.
+//!
+//! The parameters of a def are inferred from its uses of `$args`: reading
+//! `$args[N]` needs at least N + 1 arguments, and any other use of `$args`
+//! accepts any number of them. A def not using `$args` takes none.
+//!
+//! Failed checks are reported as errors, and the compilation goes on (see
+//! [crate::RecoveryExt]). Only arguments a def does not use are warned
+//! about instead (see [crate::lints]): the def may ignore them on purpose.
+use {
+    super::{
+        lints, sym, At, Compiler, Diagnostic, DiagnosticExt, LintExt, Mut, RecoveryExt, Result,
+        SymInfo,
+    },
+    sym::{LitType, Params, Typ},
+};
+
+/// A call with fewer arguments than its def reads.
+pub const WRONG_ARG_COUNT: &str = "wrong-arg-count";
+/// The value of a def that returns no output.
+pub const DEF_AS_STRING: &str = "def-as-string";
+/// A slice of a value that is not an array.
+pub const SLICE_OF_NON_ARRAY: &str = "slice-of-non-array";
+/// A use of a def that is not `pub` from another file than its own.
+pub const PRIVATE_DEF: &str = "private-def";
+
+pub trait CheckExt: Mut<Compiler> {
+    /// Start inferring the parameters of a def, about to be compiled.
+    fn enter_params(&mut self) {
+        self.borrow_mut().params.push(Params::NONE);
+    }
+
+    /// The parameters of the def just compiled.
+    fn exit_params(&mut self) -> Params {
+        self.borrow_mut().params.pop().unwrap_or(Params::ANY)
+    }
+
+    /// Give up inferring the parameters of the current def, whose failed
+    /// items may hide uses of `$args`.
+    fn forget_params(&mut self) {
+        if let Some(params) = self.borrow_mut().params.last_mut() {
+            *params = Params::ANY;
+        }
+    }
+
+    /// Note a use of `$args`, either whole or by `range`.
+    fn use_args(&mut self, range: Option<&ast::Range>) {
+        let Some(params) = self.borrow_mut().params.last_mut() else {
+            return;
+        };
+        match range {
+            Some(ast::Range::Index(ast::InvocationArg::Natural(ast::Natural((n,))))) => {
+                let nargs = n.parse::<usize>().map_or(0, |n| n + 1);
+                params.min = params.min.max(nargs);
+                if params.max != Params::VARIADIC {
+                    params.max = params.max.max(nargs);
+                }
+            }
+            _ => params.max = Params::VARIADIC,
+        }
+    }
+
+    /// Check the number of arguments of a call to `target`, named `name`.
+    fn check_call(&mut self, name: &str, target: &SymInfo, args: &[SymInfo], at: At) -> Result<()> {
+        let Ok(addr) = target.as_addr_ref() else {
+            return Ok(());
+        };
+        // Spread values make the argument count a runtime matter
+        if target.is_extern() || args.iter().any(may_spread) {
+            return Ok(());
+        }
+        let nargs: usize = args.iter().map(|arg| arg.typ.size() as usize).sum();
+        let params = addr.params;
+        if params.accepts(nargs) {
+            return Ok(());
+        }
+
+        let note = format!("the arguments of {name} are inferred from its uses of $args");
+        let cmp = self.borrow_mut();
+        if nargs > params.max {
+            let diagnostic = Diagnostic::warning(
+                lints::SURPLUS_ARGS,
+                format!("{name} uses {}, but is given {nargs}", describe(params)),
+            )
+            .with_note(note);
+            let diagnostic = cmp.at(diagnostic, at, "called here");
+            return cmp.warn(diagnostic);
+        }
+        let diagnostic = Diagnostic::error(
+            WRONG_ARG_COUNT,
+            format!("{name} takes {}, but is given {nargs}", describe(params)),
+        )
+        .with_note(note);
+        let diagnostic = cmp.at(diagnostic, at, "called here");
+        cmp.report(diagnostic)
+    }
+
+    /// Check that the def `name`, found as `sinfo`, has an output to use as
+    /// the value of `$name`.
+    fn check_def_value(&mut self, name: &str, sinfo: &SymInfo) -> Result<()> {
+        let Ok(addr) = sinfo.as_addr_ref() else {
+            return Ok(());
+        };
+        if !returns_nothing(&addr.ret_t) {
+            return Ok(());
+        }
+        let diagnostic = Diagnostic::error(
+            DEF_AS_STRING,
+            format!("{name} returns no output to use as a string"),
+        )
+        .with_note(format!(
+            "a def ending with a statement returns nothing; end {name} with an invocation"
+        ));
+        let cmp = self.borrow_mut();
+        let diagnostic = cmp.at(diagnostic, At::Text(name), "used here");
+        cmp.report(diagnostic)
+    }
+
+    /// Check that the def `name`, found as `sinfo`, is defined in the
+    /// current file, or else declared with `pub def`.
+    fn check_visible(&mut self, name: &str, sinfo: &SymInfo) -> Result<()> {
+        let cmp = self.borrow_mut();
+        let Ok(addr) = sinfo.as_addr_ref() else {
+            return Ok(());
+        };
+        if addr.linkage != sym::Linkage::Internal {
+            return Ok(());
+        }
+        let (Some(source), Some(file)) = (cmp.sources.last(), cmp.usage.def_file(addr.addr)) else {
+            return Ok(());
+        };
+        if file == source.file {
+            return Ok(());
+        }
+
+        let diagnostic = Diagnostic::error(
+            PRIVATE_DEF,
+            format!("{name} is private to {}", cmp.debug_info.files[file]),
+        )
+        .with_note(format!(
+            "declare it with pub def {name} to use it from other files"
+        ));
+        let diagnostic = cmp.at(diagnostic, At::Text(name), "used here");
+        cmp.report(diagnostic)
+    }
+
+    /// Check that `$name`, found as `sinfo`, is an array to slice.
+    fn check_slice(&mut self, name: &str, sinfo: &SymInfo) -> Result<()> {
+        if !is_scalar(sinfo) {
+            return Ok(());
+        }
+        let what = match &sinfo.typ {
+            Typ::Address(_) => "the output of a def",
+            Typ::Local(_) if is_job(sinfo) => "the output of a command",
+            _ => "a string",
+        };
+        let diagnostic = Diagnostic::error(
+            SLICE_OF_NON_ARRAY,
+            format!("${name} is {what}, not an array"),
+        )
+        .with_note("only $args and its slices can be sliced");
+        let cmp = self.borrow_mut();
+        let diagnostic = cmp.at(diagnostic, At::Text(name), "sliced here");
+        cmp.report(diagnostic)
+    }
+}
+
+impl<C: Mut<Compiler>> CheckExt for C {}
+
+/// How many arguments `params` takes, in words.
+fn describe(params: Params) -> String {
+    let arguments = |n| if n == 1 { "argument" } else { "arguments" };
+    match params {
+        Params { min, max } if min == max => format!("{min} {}", arguments(min)),
+        Params {
+            min,
+            max: Params::VARIADIC,
+        } => format!("at least {min} {}", arguments(min)),
+        Params { min, max } => format!("{min} to {max} arguments"),
+    }
+}
+
+/// Whether `sinfo` is surely a single value, and not an array.
+fn is_scalar(sinfo: &SymInfo) -> bool {
+    match &sinfo.typ {
+        Typ::Literal(lit) => matches!(
+            lit.lit_type,
+            LitType::String | LitType::Natural | LitType::Null
+        ),
+        Typ::Address(addr) => is_scalar(&addr.ret_t),
+        // An array literal (see sym::Typ::array)
+        Typ::Local(local) if local.is_alias && local.fp_off == 0 => false,
+        Typ::Local(local) => !local.types.is_empty() && local.types.iter().all(is_scalar),
+    }
+}
+
+/// Whether `sinfo` may hold an array, spread into many arguments.
+fn may_spread(sinfo: &SymInfo) -> bool {
+    match &sinfo.typ {
+        Typ::Literal(lit) => matches!(lit.lit_type, LitType::Args | LitType::Syscall),
+        Typ::Address(addr) => may_spread(&addr.ret_t),
+        Typ::Local(local) => local.types.iter().any(may_spread),
+    }
+}
+
+/// Whether `sinfo`, a scalar, is the output of a command: the return
+/// value of an invocation, rather than a literal.
+fn is_job(sinfo: &SymInfo) -> bool {
+    match &sinfo.typ {
+        Typ::Local(local) if !local.is_alias => true,
+        Typ::Local(local) => local.types.iter().all(is_job),
+        _ => false,
+    }
+}
+
+/// Whether a def returning `ret_t` returns no output, like a def ending
+/// with a statement, which returns `0`.
+fn returns_nothing(ret_t: &SymInfo) -> bool {
+    match &ret_t.typ {
+        Typ::Literal(lit) => matches!(lit.lit_type, LitType::Natural | LitType::Null),
+        _ => false,
+    }
+}
@@ -1,6 +1,6 @@
 use super::{
-    facade, i, sym, te, temg, Borrow, BorrowMut, Compiler, EmitExt, Result, ScopesRef, SymInfo,
-    SymbolTableExt,
+    facade, i, recovery, sym, te, temg, terr, At, Borrow, BorrowMut, CheckExt, Compiler,
+    Diagnostic, DiagnosticExt, EmitExt, LintExt, Result, ScopesRef, SymInfo, SymbolTableExt,
 };
 
 pub trait CompileUtil: Borrow<Compiler> + BorrowMut<Compiler> {
@@ -26,6 +26,13 @@ pub trait CompileUtil: Borrow<Compiler> + BorrowMut<Compiler> {
 
     fn compile_slice(&mut self, ast::Slice((name, box_range)): ast::Slice) -> Result<SymInfo> {
         let cmp = self.cmp();
+        if name == "args" {
+            cmp.use_args(Some(&box_range));
+        } else if let Ok(sinfo) = cmp.lookup(name) {
+            let sinfo = sinfo.to_owned();
+            te!(cmp.check_slice(name, &sinfo));
+        }
+        let params = cmp.params.last().copied();
         let ast = facade::parse_invocation(
             r###"
                         __syscall-argslice 0 0 $args
@@ -45,7 +52,12 @@ pub trait CompileUtil: Borrow<Compiler> + BorrowMut<Compiler> {
             }
         }
         args.push(source);
-        cmp.compile(ast)
+        let sinfo = te!(cmp.compile(ast));
+        // The whole `$args` given to the argslice is not a use of them all
+        if let (Some(last), Some(params)) = (cmp.params.last_mut(), params) {
+            *last = params;
+        }
+        Ok(sinfo)
     }
 
     fn compile_variable_as_auto(
@@ -53,19 +65,31 @@ pub trait CompileUtil: Borrow<Compiler> + BorrowMut<Compiler> {
         ast::Variable((var,)): ast::Variable,
     ) -> Result<SymInfo> {
         let cmp = self.cmp();
-        match te!(cmp.lookup(var)) {
+        let sinfo = match cmp.lookup(var) {
+            Ok(sinfo) => sinfo,
+            Err(_) if cmp.poison.contains(var) => terr!(recovery::Poisoned),
+            Err(_) => terr!(cmp.unknown_symbol(var)),
+        }
+        .to_owned();
+        cmp.use_variable(var, &sinfo);
+        match &sinfo {
             sinfo @ SymInfo {
                 typ: sym::Typ::Literal(_),
                 ..
             } => Ok(sinfo.to_owned()),
+            // A val, visible in nested defs as well
+            sinfo if sinfo.is_constant() => Ok(sinfo.to_owned()),
             sinfo @ SymInfo {
                 typ: sym::Typ::Local(_),
                 ..
             } => cmp.ensure_local_scope(var, sinfo),
-            &SymInfo {
+            sinfo @ &SymInfo {
                 typ: sym::Typ::Address(_),
                 ..
-            } => cmp.capture_call_to_local_var(var),
+            } => {
+                te!(cmp.check_def_value(var, sinfo));
+                cmp.capture_call_to_local_var(var)
+            }
         }
     }
 
@@ -93,13 +117,19 @@ pub trait CompileUtil: Borrow<Compiler> + BorrowMut<Compiler> {
         if *scope_id == cmp.current_scope_id() {
             Ok(sinfo.to_owned())
         } else {
-            temg!(
+            error::ldebug!(
                 "{} is in scope {} instead of {} ({:?})",
                 var,
                 scope_id,
                 cmp.current_scope_id(),
                 sinfo
+            );
+            let diagnostic = Diagnostic::error(
+                "outer-local",
+                format!("{var} is a local of an enclosing def"),
             )
+            .with_note("the locals of a def are not visible inside the defs nested in it");
+            terr!(cmp.at(diagnostic, At::Text(var), "used here"))
         }
     }
 
@@ -118,7 +148,7 @@ pub trait CompileUtil: Borrow<Compiler> + BorrowMut<Compiler> {
         let sinfo = sinfo.to_owned();
         Ok(match &sinfo {
             &SymInfo {
-                typ: sym::Typ::Address(sym::Address { addr, .. }),
+                typ: sym::Typ::Address(sym::Address { addr, linkage, .. }),
                 ..
             } => {
                 let instr = if push_or_retval {
@@ -127,7 +157,12 @@ pub trait CompileUtil: Borrow<Compiler> + BorrowMut<Compiler> {
                 } else {
                     i::RetFuncAddr
                 };
-                cmp.emit1(instr(addr))
+                cmp.emit1(instr(addr));
+                if linkage == sym::Linkage::Extern {
+                    // `addr` is the extern id; the linker patches the operand
+                    let site = (cmp.instr_id(), addr);
+                    cmp.relocs.sites.push(site);
+                }
             }
             &SymInfo {
                 typ:
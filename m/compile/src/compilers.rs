@@ -27,6 +27,12 @@ pub trait Compilers<'i> {
                 te!(cmp.emit_cleanup(i::Collect, &sinfo));
                 Ok(sinfo)
             }
+            ast::Item::ValStmt(ast::ValStmt((name, expr))) => {
+                let sinfo = te!(constant(cmp, name, expr));
+                ldebug!("type (val) {}: {:?}", name, sinfo);
+                cmp.alias_in_scope(&sinfo, name);
+                Ok(sinfo)
+            }
             ast::Item::SrcStmt(ast::SrcStmt((name, expr))) => {
                 let sinfo = te!(cmp.compile(expr));
                 ldebug!("type (src) {}: {:?}", name, sinfo);
@@ -34,38 +40,42 @@ pub trait Compilers<'i> {
                 te!(cmp.emit_cleanup(i::Pipe, &sinfo));
                 Ok(sinfo)
             }
-            ast::Item::DefStmt(ast::DefStmt((name, body))) => {
-                cmp.emit1(i::Jump { addr: 0 });
-                let jump_instr = cmp.instr_id();
-
-                cmp.emit1(i::Allocate { size: 0 });
-                let alloc_instr = cmp.instr_id();
-
-                cmp.enter_scope();
-                let retval = te!(cmp.compile(body));
-                let frame_size = cmp.stack_frame_size();
-
-                te!(cmp.emit_from_symbol(false, &retval));
-
-                cmp.exit_scope();
-
-                te!(cmp.backpatch_with(alloc_instr, frame_size));
-                cmp.emit1(i::Return(frame_size));
-
-                let jump_target = cmp.instr_id() + 1;
-                te!(cmp.backpatch_with(jump_instr, jump_target));
-
-                let ninfo = cmp.new_address(name, jump_instr + 1, &retval);
-                ldebug!("type (def) {}: {:?}", name, ninfo);
+            ast::Item::DefStmt(def) => def_stmt(cmp, def, sym::Linkage::Internal),
+            ast::Item::PubDefStmt(ast::PubDefStmt((def,))) => {
+                // Only globals (scope 1) can be exported
+                if cmp.current_scope_id() != 1 {
+                    let ast::DefStmt((name, _)) = def;
+                    let diagnostic = error::Diagnostic::error(
+                        "misplaced-pub-def",
+                        format!("pub def {name} is not at the top level"),
+                    )
+                    .with_note("only top-level defs can be exported");
+                    terr!(cmp.at(diagnostic, At::Text(name), "defined here"))
+                }
+                def_stmt(cmp, def, sym::Linkage::Public)
+            }
+            ast::Item::ExternStmt(ast::ExternStmt((name,))) => {
+                let extern_id = cmp.relocs.extern_id(name);
+                let ninfo = cmp.new_extern_address(name, extern_id);
+                ldebug!("type (extern def) {}: {:?}", name, ninfo);
 
                 Ok(ninfo)
             }
             ast::Item::Include(ast::Include((path,))) => {
-                te!(
-                    IncludeExt::include(cmp, path.to_string().as_str()),
-                    "Including: {}",
-                    path
-                );
+                let path = path_text(&path);
+                match IncludeExt::include(cmp, path) {
+                    Err(Error {
+                        kind: ErrorKind::Io(err),
+                        ..
+                    }) => {
+                        cmp.poison_everything();
+                        let diagnostic =
+                            Diagnostic::error("include-failed", format!("cannot include {path}"))
+                                .with_note(err.to_string());
+                        terr!(cmp.at(diagnostic, At::Text(path), "included here"))
+                    }
+                    result => te!(result, "Including: {}", path),
+                }
                 Ok(SymInfo::NULL)
             }
             ast::Item::IncludeStr(ast::IncludeStr((ident, path))) => {
@@ -83,7 +93,11 @@ pub trait Compilers<'i> {
         |cmp, expr| match expr {
             ast::Expr::String(s) => cmp.compile(s),
             ast::Expr::Natural(n) => cmp.compile(n),
-            ast::Expr::Invocation(invc) => cmp.compile(invc),
+            ast::Expr::Invocation(invc) => {
+                let span = (invc.0).7;
+                cmp.compile(invc)
+                    .map_err(|err| cmp.diagnose(err, At::Span(span)))
+            }
             ast::Expr::Variable(var) => cmp.compile_variable_as_auto(var),
             ast::Expr::Slice(slice) => cmp.compile_slice(slice),
             ast::Expr::Array(closure) => cmp.compile_array(closure),
@@ -92,7 +106,21 @@ pub trait Compilers<'i> {
     fn block() -> S<Block<'i>> {
         |cmp, ast::Block((items, expr))| {
             for item in items {
-                te!(cmp.compile(item));
+                let name = defined_name(&item);
+                let binding = match &item {
+                    ast::Item::LetStmt(_) | ast::Item::ValStmt(_) | ast::Item::SrcStmt(_) => name,
+                    _ => None,
+                };
+                let start = cmp.num_instrs();
+                match cmp.compile(item) {
+                    Ok(_) => {
+                        if let Some(name) = binding {
+                            let code = start..cmp.num_instrs();
+                            cmp.bind(name, code)
+                        }
+                    }
+                    Err(err) => te!(cmp.recover(err, name)),
+                }
             }
             cmp.compile(expr)
         }
@@ -125,7 +153,16 @@ pub trait Compilers<'i> {
             // Allocate minimal stack for call tmp local variables
             const CALL_CTX: usize = 7;
             cmp.emit1(i::Allocate { size: CALL_CTX });
-            te!(cmp.compile(program));
+            let result = cmp.compile(program);
+            cmp.share_reused_sources();
+            let warned = cmp.warn_unused_bindings();
+            // Sources are found by the addresses of their text, which may
+            // be reused once the module is compiled
+            cmp.pop_source();
+            te!(warned);
+            // A failure after reported errors is likely caused by them
+            te!(cmp.check_errors());
+            te!(result);
             cmp.emit1(i::Return(CALL_CTX));
             Ok(SymInfo::NULL)
         }
@@ -140,11 +177,23 @@ pub trait Compilers<'i> {
             output_redirections,
             envs,
             mut args,
+            (span_start, span_end),
         ))| {
+            let span = (span_start, span_end);
             // TODO
             if !output_redirections.is_empty() {
-                error::lwarn!("ignoring output redirections");
+                let diagnostic =
+                    Diagnostic::warning(lints::IGNORED_SETTING, "output redirections are ignored")
+                        .with_note("output redirections are not supported yet");
+                let diagnostic = cmp.at(diagnostic, At::Span(span), "redirects its output");
+                te!(cmp.warn(diagnostic));
             }
+            // Settings a def invocation ignores
+            let settings = [
+                (cwd_opt.is_some(), "cwd"),
+                (!envs.is_empty(), "environment"),
+                (!input_redirections.is_empty(), "input redirections"),
+            ];
 
             // === Parsings ===
             //
@@ -163,7 +212,24 @@ pub trait Compilers<'i> {
             let inp_redir_sinfos = te!(cmp.compile(input_redirections));
             // target
             let invctrgt = format!("{}", invocation_target);
+            let target_name = match &invocation_target {
+                ast::InvocationTarget::InvocationTargetLocal(ast::InvocationTargetLocal((
+                    name,
+                ))) => Some(*name),
+                _ => None,
+            };
             let invc_target_sinfo = te!(cmp.compile(invocation_target));
+            if let sym::Typ::Address(_) = invc_target_sinfo.typ {
+                for (_, setting) in settings.into_iter().filter(|&(given, _)| given) {
+                    let diagnostic = Diagnostic::warning(
+                        lints::IGNORED_SETTING,
+                        format!("the {setting} of a def invocation is ignored"),
+                    )
+                    .with_note("cwd, environment and redirections only apply to commands");
+                    let diagnostic = cmp.at(diagnostic, At::Span(span), "invokes a def");
+                    te!(cmp.warn(diagnostic));
+                }
+            }
             // cwd
             let cwd_sinfo = if let Some(cwd) = cwd_opt {
                 te!(cmp.compile(cwd))
@@ -173,8 +239,20 @@ pub trait Compilers<'i> {
             // args
             args.reverse();
             let args_sinfos = te!(cmp.compile(args));
+            if let Some(name) = target_name {
+                // Captures of `$name` are synthesized, without a span
+                let at = if span_start < span_end {
+                    At::Span(span)
+                } else {
+                    At::Text(name)
+                };
+                te!(cmp.check_call(name, &invc_target_sinfo, &args_sinfos, at));
+            }
 
             // === Emits ===
+            if span_start < span_end {
+                cmp.record_location(span_start);
+            }
             // RetVal Allocation
             let mut retval_si = match &invc_target_sinfo {
                 SymInfo {
@@ -327,7 +405,10 @@ pub trait Compilers<'i> {
                 A::Opt(opt) => cmp.compile(opt),
                 A::String(s) => cmp.compile(s),
                 A::Ident(id) => cmp.compile_text(id),
-                A::Variable(ast::Variable(("args",))) => Ok(SymInfo::args()),
+                A::Variable(ast::Variable(("args",))) => {
+                    cmp.use_args(None);
+                    Ok(SymInfo::args())
+                }
                 A::Slice(slice) => cmp.compile_slice(slice),
                 A::Variable(var) => cmp.compile_variable_as_auto(var),
                 A::Path(path) => cmp.compile(path),
@@ -375,5 +456,117 @@ pub trait Compilers<'i> {
     }
 }
 
+fn def_stmt(
+    cmp: &mut Compiler,
+    ast::DefStmt((name, body)): DefStmt,
+    linkage: sym::Linkage,
+) -> Result<SymInfo> {
+    cmp.emit1(i::Jump { addr: 0 });
+    let jump_instr = cmp.instr_id();
+
+    cmp.emit1(i::Allocate { size: 0 });
+    let alloc_instr = cmp.instr_id();
+
+    cmp.enter_scope();
+    cmp.enter_params();
+    let body_result = cmp.compile(body).and_then(|retval| {
+        let frame_size = cmp.stack_frame_size();
+        te!(cmp.emit_from_symbol(false, &retval));
+        Ok((retval, frame_size))
+    });
+    // Leave the scope even on failure, to recover from it (see RecoveryExt)
+    cmp.exit_scope();
+    let params = cmp.exit_params();
+    let (retval, frame_size) = te!(body_result);
+
+    te!(cmp.backpatch_with(alloc_instr, frame_size));
+    cmp.emit1(i::Return(frame_size));
+
+    let jump_target = cmp.instr_id() + 1;
+    te!(cmp.backpatch_with(jump_instr, jump_target));
+
+    te!(cmp.check_def_shadowing(name));
+    let ninfo = match linkage {
+        sym::Linkage::Public => cmp.new_public_address(name, jump_instr + 1, &retval, params),
+        _ => cmp.new_address(name, jump_instr + 1, &retval, params),
+    };
+    cmp.record_def(jump_instr + 1, name);
+    cmp.record_def_source(jump_instr + 1, name);
+    ldebug!("type (def) {}: {:?}", name, ninfo);
+
+    Ok(ninfo)
+}
+
+/// The value of `expr`, bound to the val `name`: a literal, another val, or
+/// an array of them, which concatenates the arrays in it. Nothing is emitted,
+/// and uses of `name` push the literals themselves.
+fn constant(cmp: &mut Compiler, name: &str, expr: ast::Expr) -> Result<SymInfo> {
+    let (message, at) = match expr {
+        ast::Expr::String(_) | ast::Expr::Natural(_) => return cmp.compile(expr),
+        ast::Expr::Array(ast::Array((items,))) => {
+            let mut types = Vec::new();
+            for item in items {
+                types.push(te!(constant(cmp, name, item)));
+            }
+            return Ok(cmp.new_array(types, "an_array"));
+        }
+        ast::Expr::Variable(var @ ast::Variable((text,))) => {
+            let sinfo = match cmp.lookup(text) {
+                Ok(sinfo) => sinfo.to_owned(),
+                // Let it fail like any unknown variable
+                Err(_) if text != "args" => return cmp.compile_variable_as_auto(var),
+                Err(_) => SymInfo::args(),
+            };
+            cmp.use_variable(text, &sinfo);
+            if sinfo.is_constant() {
+                return Ok(sinfo);
+            }
+            (
+                format!("val {name} uses ${text}, which is not a constant"),
+                At::Text(text),
+            )
+        }
+        ast::Expr::Slice(ast::Slice((text, _))) => (
+            format!("val {name} slices ${text}, which is not a constant"),
+            At::Text(text),
+        ),
+        ast::Expr::Invocation(ast::Invocation((.., span))) => (
+            format!("val {name} runs a command"),
+            if span.0 < span.1 {
+                At::Span(span)
+            } else {
+                At::Text(name)
+            },
+        ),
+    };
+    let diagnostic = Diagnostic::error("val-not-constant", message).with_note(
+        "a val is computed when compiling, from literals, arrays and other vals; \
+         bind it with let to compute it when running",
+    );
+    terr!(cmp.at(diagnostic, at, "not a constant"))
+}
+
+/// The name `item` defines, if any.
+fn defined_name<'i>(item: &ast::Item<'i>) -> Option<&'i str> {
+    match item {
+        ast::Item::LetStmt(ast::LetStmt((name, _)))
+        | ast::Item::ValStmt(ast::ValStmt((name, _)))
+        | ast::Item::SrcStmt(ast::SrcStmt((name, _)))
+        | ast::Item::DefStmt(ast::DefStmt((name, _)))
+        | ast::Item::PubDefStmt(ast::PubDefStmt((ast::DefStmt((name, _)),)))
+        | ast::Item::IncludeStr(ast::IncludeStr((name, _))) => Some(name),
+        _ => None,
+    }
+}
+
+/// The text of `path`, as a slice of the source.
+fn path_text<'i>(path: &ast::Path<'i>) -> &'i str {
+    match path {
+        ast::Path::HomePath(ast::HomePath((p,)))
+        | ast::Path::AbsPath(ast::AbsPath((p,)))
+        | ast::Path::RelPath(ast::RelPath((p,))) => p,
+    }
+}
+
 pub struct CompilersImpl;
 impl<'i> Compilers<'i> for CompilersImpl {}
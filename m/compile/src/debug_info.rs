@@ -0,0 +1,110 @@
+use {
+    super::{Compiler, Mut},
+    std::ops::Range,
+};
+
+/// The text of a file being compiled, for turning span offsets into lines
+/// and columns.
+#[derive(Debug, Clone)]
+pub(crate) struct Source {
+    pub file: usize,
+    pub text: String,
+    /// The addresses of the text the AST was parsed from, to find the
+    /// offsets of the `&str`s in the AST.
+    addrs: Range<usize>,
+    line_starts: Vec<usize>,
+    /// The offset of the include path in the including source.
+    pub included_at: Option<usize>,
+}
+
+impl Source {
+    fn new(file: usize, text: &str, included_at: Option<usize>) -> Self {
+        let start = text.as_ptr() as usize;
+        Self {
+            file,
+            text: text.to_owned(),
+            addrs: start..start + text.len(),
+            line_starts: std::iter::once(0)
+                .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+                .collect(),
+            included_at,
+        }
+    }
+
+    /// The offset of `text`, if it is a slice of this source.
+    pub fn offset_of(&self, text: &str) -> Option<usize> {
+        let addr = text.as_ptr() as usize;
+        let end = addr + text.len();
+        (self.addrs.contains(&addr) && end <= self.addrs.end).then(|| addr - self.addrs.start)
+    }
+
+    /// The 1-based line and column of `offset`.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        (line, offset - self.line_starts[line - 1] + 1)
+    }
+
+    /// The text of a 1-based line, without its newline.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.text.len(), |&next| next - 1);
+        &self.text[start..end]
+    }
+}
+
+pub trait DebugInfoExt: Mut<Compiler> {
+    /// Enter the text of the current file path (see
+    /// [FilePathExt](super::FilePathExt)).
+    fn push_source(&mut self, text: &str) {
+        let cmp = self.cmp();
+        let path = cmp.current_file_path.last().cloned().unwrap_or_default();
+        let file = cmp.debug_info.add_file(&path);
+        cmp.sources.push(Source::new(file, text, None));
+    }
+
+    /// Like [Self::push_source], for a file included by the current source,
+    /// where `include_path` is the path text of the include item.
+    fn push_included_source(&mut self, text: &str, include_path: &str) {
+        let included_at = self
+            .cmp()
+            .sources
+            .last()
+            .and_then(|source| source.offset_of(include_path));
+        self.push_source(text);
+        self.cmp().sources.last_mut().unwrap().included_at = included_at;
+    }
+
+    fn pop_source(&mut self) {
+        self.cmp().sources.pop();
+    }
+
+    /// Attribute the next emitted instruction to `offset` in the current
+    /// source.
+    fn record_location(&mut self, offset: usize) {
+        let cmp = self.cmp();
+        let Some(source) = cmp.sources.last() else {
+            return;
+        };
+        let (line, column) = source.position(offset);
+        let location = vm::debug_info::Location {
+            instr: cmp.icode.instructions.len(),
+            file: source.file,
+            line,
+            column,
+        };
+        cmp.debug_info.add_location(location);
+    }
+
+    fn record_def(&mut self, addr: usize, name: &str) {
+        self.cmp().debug_info.defs.push((addr, name.to_owned()));
+    }
+
+    fn cmp(&mut self) -> &mut Compiler {
+        self.borrow_mut()
+    }
+}
+
+impl<S: Mut<Compiler>> DebugInfoExt for S {}
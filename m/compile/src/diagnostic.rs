@@ -0,0 +1,162 @@
+use {
+    super::{debug_info::Source, terr, Compiler, Error, ErrorKind, Ref, Result},
+    error::diagnostic::{Diagnostic, Label, Position},
+};
+
+/// Where a diagnostic points to in the sources being compiled.
+#[derive(Debug, Copy, Clone)]
+pub enum At<'t> {
+    /// A slice of the source text, like an identifier from the AST.
+    Text(&'t str),
+    /// A span in the current source.
+    Span(ast::Span<'t>),
+}
+
+pub trait DiagnosticExt: Ref<Compiler> {
+    /// A label on `at`, unless it is not in any source (like the text of
+    /// synthesized code).
+    fn label(&self, at: At, message: impl Into<String>) -> Option<Label> {
+        let (idx, start, end) = self.locate(at)?;
+        let source = &self.borrow().sources[idx];
+        let position = position(self.borrow(), source, start);
+        let source_line = source.line(position.line).to_owned();
+        let len = (end - start).min(source_line.len() + 1 - position.column);
+        Some(Label {
+            position,
+            len,
+            source_line,
+            message: message.into(),
+        })
+    }
+
+    /// Point `diagnostic` at `at`, along with the chain of includes that
+    /// led there.
+    fn at(&self, mut diagnostic: Diagnostic, at: At, message: impl Into<String>) -> Diagnostic {
+        let cmp = self.borrow();
+        if let Some((idx, _, _)) = self.locate(at) {
+            diagnostic.included_from = (1..=idx)
+                .rev()
+                .filter_map(|idx| {
+                    let offset = cmp.sources[idx].included_at?;
+                    Some(position(cmp, &cmp.sources[idx - 1], offset))
+                })
+                .collect();
+        }
+        diagnostic.primary = self.label(at, message);
+        diagnostic
+    }
+
+    /// Turn a plain error message into a diagnostic at `at`. Other errors
+    /// are kept as they are.
+    fn diagnose(&self, err: Error, at: At) -> Error {
+        let Error { kind, trace } = err;
+        let message = match kind {
+            ErrorKind::Msg(msg) => msg.to_owned(),
+            ErrorKind::Message(msg) => msg,
+            kind => return Error { kind, trace },
+        };
+        let diagnostic = Diagnostic::error("compile-error", message);
+        Error {
+            kind: ErrorKind::Diagnostic(Box::new(self.at(diagnostic, at, "in this invocation"))),
+            trace,
+        }
+    }
+
+    fn unknown_symbol(&self, name: &str) -> Diagnostic {
+        let diagnostic = Diagnostic::error("unknown-symbol", format!("Symbol not found: {name}"));
+        self.at(diagnostic, At::Text(name), "not found in this scope")
+    }
+
+    /// The diagnostics of the syntax errors in `err`, which come from
+    /// parsing the current source.
+    fn syntax_errors(&self, err: parse::Error) -> Result<Vec<Diagnostic>> {
+        use parse::LocationError as P;
+
+        let (_, errors) = match err.kind {
+            parse::ErrorKind::Syntax(errors) => errors,
+            kind => terr!(parse::Error { kind, ..err }),
+        };
+        let text = self
+            .borrow()
+            .sources
+            .last()
+            .map_or("", |source| &source.text);
+        let token = |start: usize, end: usize| text.get(start..end).unwrap_or_default();
+        let expected_one_of = |expected: Vec<String>| match expected.as_slice() {
+            [] => None,
+            [one] => Some(format!("expected {one}")),
+            many => Some(format!("expected one of {}", many.join(", "))),
+        };
+
+        Ok(errors
+            .into_iter()
+            .map(|err| {
+                let (message, span, label, expected) = match err {
+                    P::InvalidToken { location } => {
+                        let next = text.get(location..).and_then(|rest| rest.chars().next());
+                        let len = next.map_or(1, char::len_utf8);
+                        (
+                            format!("unexpected character `{}`", token(location, location + len)),
+                            (location, location + len),
+                            "not a token",
+                            vec![],
+                        )
+                    }
+                    P::UnrecognizedEof { location, expected } => (
+                        "unexpected end of file".to_owned(),
+                        (location, location + 1),
+                        "the file ends here",
+                        expected,
+                    ),
+                    P::UnrecognizedToken {
+                        token: (start, _, end),
+                        expected,
+                    } => (
+                        format!("unexpected `{}`", token(start, end)),
+                        (start, end),
+                        "unexpected token",
+                        expected,
+                    ),
+                    P::ExtraToken {
+                        token: (start, _, end),
+                    } => (
+                        format!("extra `{}`", token(start, end)),
+                        (start, end),
+                        "extra token",
+                        vec![],
+                    ),
+                    P::User { .. } => unreachable!("lexer errors are not syntax errors"),
+                };
+                let mut diagnostic = Diagnostic::error("syntax-error", message);
+                diagnostic.notes.extend(expected_one_of(expected));
+                self.at(diagnostic, At::Span(span), label)
+            })
+            .collect())
+    }
+
+    /// The source index, start and end offsets of `at`.
+    fn locate(&self, at: At) -> Option<(usize, usize, usize)> {
+        let sources = &self.borrow().sources;
+        match at {
+            At::Text(text) => sources.iter().enumerate().rev().find_map(|(idx, source)| {
+                let start = source.offset_of(text)?;
+                Some((idx, start, start + text.len()))
+            }),
+            At::Span((start, end)) if start < end => {
+                Some((sources.len().checked_sub(1)?, start, end))
+            }
+            At::Span(_) => None,
+        }
+    }
+}
+
+impl<C: Ref<Compiler>> DiagnosticExt for C {}
+
+fn position(cmp: &Compiler, source: &Source, offset: usize) -> Position {
+    let (line, column) = source.position(offset);
+    Position {
+        file: cmp.debug_info.files[source.file].to_owned(),
+        line,
+        column,
+    }
+}
@@ -1,24 +1,25 @@
 use super::{te, Result};
 
+/// Parse a synthesized invocation. Its span is cleared, since `src` is not
+/// the text of the file being compiled.
 pub fn parse_invocation(src: &str) -> Result<ast::Invocation> {
     let lex = parse::lex::Lex::new(src);
     let parser = parse::dust::InvocationParser::new();
-    let res = parser.parse(lex).map_err(parse::map_err(src));
-    Ok(te!(res))
+    let res = parser.parse(&mut vec![], lex).map_err(parse::map_err(src));
+    let mut invocation = te!(res);
+    (invocation.0).7 = <_>::default();
+    Ok(invocation)
 }
 
 pub fn parse_expr(src: &str) -> Result<ast::Expr> {
     let lex = parse::lex::Lex::new(src);
     let parser = parse::dust::ExprParser::new();
-    let res = parser.parse(lex).map_err(parse::map_err(src));
+    let res = parser.parse(&mut vec![], lex).map_err(parse::map_err(src));
     Ok(te!(res))
 }
 
 pub fn parse_block(src: &str) -> Result<ast::Block> {
-    let lex = parse::lex::Lex::new(src);
-    let parser = parse::dust::BlockParser::new();
-    let res = parser.parse(lex).map_err(parse::map_err(src));
-    Ok(te!(res))
+    Ok(te!(parse::parse_block(src)))
 }
 
 pub fn make_forward_invocation<'a>(func_name: &'a str) -> ast::Invocation<'a> {
@@ -30,18 +31,21 @@ pub fn make_forward_invocation<'a>(func_name: &'a str) -> ast::Invocation<'a> {
         vec![],
         vec![],
         vec![ast::InvocationArg::Variable(ast::Variable(("args",)))],
+        <_>::default(),
     ))
 }
 pub fn compile_invocation_block(
     cmp: &mut super::Compiler,
     program: ast::Block,
 ) -> Result<super::SymInfo> {
-    use super::{i, EmitExt};
+    use super::{i, EmitExt, RecoveryExt};
 
     // Allocate minimal stack for call tmp local variables
     const CALL_CTX: usize = 8;
     cmp.emit1(i::Allocate { size: CALL_CTX });
-    let sinfo = te!(cmp.compile(program));
+    let result = cmp.compile(program);
+    te!(cmp.check_errors());
+    let sinfo = te!(result);
     // TODO: why is this sinfo pointing to the God and not to ret-addr?
     //eprintln!("{sinfo:?}");
     //te!(super::CompileUtil::emit_cleanup(cmp, i::CleanUp, &sinfo));
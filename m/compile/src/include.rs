@@ -1,13 +1,16 @@
-use super::{facade, fs, te, Compiler, FilePathExt, Mut, Result, SymInfo, SymbolTableExt};
+use super::{
+    fs, recovery, te, terr, Compiler, DebugInfoExt, FilePathExt, Mut, RecoveryExt, Result, SymInfo,
+    SymbolTableExt,
+};
 
 pub trait IncludeExt: Mut<Compiler> {
     fn include(&mut self, path: &str) -> Result<()> {
         let cmp = self.borrow_mut();
 
-        let input = te!((*cmp).include_file(path));
-        let block = te!(facade::parse_block(&input), "In include: {}", path);
-
-        let cmp_result = cmp.compile(block);
+        let cmp_result = match (*cmp).include_file(path) {
+            Ok(input) => cmp.compile_included(&input, path),
+            Err(err) => Err(err),
+        };
 
         let path = cmp.pop_file_path().unwrap();
         te!(cmp_result, "In including: {}", path);
@@ -44,5 +47,27 @@ trait IncludePrivate: Mut<Compiler> {
         let input = te!(fs::read_to_string(path), "Include: {}", path);
         Ok(input)
     }
+
+    /// Compile the text of an included file.
+    fn compile_included(&mut self, input: &str, path: &str) -> Result<()> {
+        let cmp = self.borrow_mut();
+
+        cmp.push_included_source(input, path);
+        let cmp_result = match parse::parse_block(input) {
+            Ok(block) => cmp.compile(block).map(|_| ()),
+            Err(err) => report_unparsed(cmp, err),
+        };
+        cmp.pop_source();
+
+        cmp_result
+    }
 }
 impl<C: Mut<Compiler>> IncludePrivate for C {}
+
+/// Report the syntax errors of an included file. They poison everything,
+/// since none of the file is compiled.
+fn report_unparsed(cmp: &mut Compiler, err: parse::Error) -> Result<()> {
+    cmp.poison_everything();
+    te!(cmp.report_syntax_errors(err));
+    terr!(recovery::Poisoned)
+}
@@ -2,7 +2,7 @@ pub const VERSION: &str = "0.0.1";
 use {
     ::show::Show,
     collection::{Deq, Map},
-    error::{te, temg},
+    error::{ldebug, te, temg, terr, Diagnostic},
     std::{
         borrow::{Borrow, Borrow as Ref, BorrowMut, BorrowMut as Mut},
         collections::HashMap,
@@ -21,17 +21,35 @@ error::Error! {
     ParseInt = num::ParseIntError
     Io = io::Error
     Sd2 = buf::sd2::Error
+    Diagnostic = Box<error::Diagnostic>
+    Diagnostics = Vec<error::Diagnostic>
+    Poisoned = recovery::Poisoned
 }
 
+// Diagnostics are big, and boxed so that every result is not as big
+impl<T> error::IntoResult<ErrorKind, T> for std::result::Result<T, Diagnostic> {
+    fn into_result(self) -> Result<T> {
+        self.map_err(Box::new).into_result()
+    }
+}
+
+pub mod checks;
 mod compile;
 mod compile_util;
 mod compiler_ext;
 mod compilers;
+mod debug_info;
+mod diagnostic;
 mod emit;
 pub mod facade;
 mod file_path;
 mod include;
 pub mod link;
+pub mod lints;
+pub mod peephole;
+pub mod prune;
+mod recovery;
+mod rewrite;
 mod sd;
 mod show;
 pub mod symbol_info;
@@ -39,14 +57,19 @@ mod symbol_table;
 use symbol_table::ScopesRef;
 pub use {
     crate::compile::{Compile, CompileEv},
+    checks::CheckExt,
     compile_util::CompileUtil,
     compiler_ext::{
         add_string, get_string_id, instrs_emit, CompilerExt, CompilerMut, VmICodeMut, VmICodeRef,
     },
     compilers::{Compilers, CompilersImpl as cmps},
+    debug_info::DebugInfoExt,
+    diagnostic::{At, DiagnosticExt},
     emit::EmitExt,
     file_path::{compute_include_path, FilePathExt},
     include::IncludeExt,
+    lints::{LintExt, Lints},
+    recovery::RecoveryExt,
     symbol_info as sym,
     symbol_table::{find_func_name, ScopeMut, ScopeRef, SymInfo, SymbolTable, SymbolTableExt},
 };
@@ -55,15 +78,40 @@ pub use {
 pub struct Compiler {
     pub icode: vm::ICode,
     pub sym_table: SymbolTable,
+    pub relocs: link::Relocs,
+    pub debug_info: vm::DebugInfo,
     pub(crate) current_file_path: Vec<String>,
+    pub(crate) sources: Vec<debug_info::Source>,
+    /// Reported errors, failing the compilation at its end (see
+    /// [RecoveryExt]).
+    pub diagnostics: Vec<Diagnostic>,
+    /// How many errors to report before giving up. 0 for no limit.
+    pub max_errors: usize,
+    pub lints: Lints,
+    pub(crate) poison: recovery::Poison,
+    pub(crate) usage: lints::Usage,
+    /// The parameters of the defs being compiled, innermost last (see
+    /// [CheckExt]).
+    pub(crate) params: Vec<sym::Params>,
 }
 
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
 impl Compiler {
     pub fn new() -> Self {
         Self {
             icode: <_>::default(),
             sym_table: <_>::default(),
+            relocs: <_>::default(),
+            debug_info: <_>::default(),
             current_file_path: <_>::default(),
+            sources: <_>::default(),
+            diagnostics: <_>::default(),
+            max_errors: DEFAULT_MAX_ERRORS,
+            lints: <_>::default(),
+            poison: <_>::default(),
+            usage: <_>::default(),
+            params: <_>::default(),
         }
     }
 
@@ -75,15 +123,31 @@ impl Compiler {
         Ok(te!(buf::sd2::ReadIn::read_in(inp)))
     }
 
-    pub fn init(&mut self, file_path: &str) -> Result<()> {
+    /// Prepare to compile the module at `file_path`, whose text is `source`.
+    /// The source is entered until the module is compiled.
+    pub fn init(&mut self, file_path: &str, source: &str) -> Result<()> {
         let cmp = self;
 
         cmp.enter_scope();
         cmp.push_file_path(file_path);
+        cmp.push_source(source);
 
         Ok(())
     }
 
+    /// Parse `source`, the module given to [Self::init]. All syntax errors
+    /// are reported as [ErrorKind::Diagnostics].
+    pub fn parse<'s>(&mut self, source: &'s str) -> Result<ast::Module<'s>> {
+        match parse::parse(source) {
+            Ok(module) => Ok(module),
+            Err(err) => {
+                te!(self.report_syntax_errors(err));
+                te!(self.check_errors());
+                unreachable!("syntax errors are errors")
+            }
+        }
+    }
+
     pub fn compile<N>(&mut self, node: N) -> Result<<Self as Compile<N>>::RetVal>
     where
         Self: Compile<N>,
@@ -187,12 +251,26 @@ impl Compiler {
         let text = text.as_ref();
         let name = text;
 
-        match te!(cmp.lookup(name)) {
-            sinfo @ SymInfo {
+        let sinfo = match cmp.lookup(name) {
+            Ok(sinfo) => sinfo,
+            Err(_) if cmp.poison.contains(name) => terr!(recovery::Poisoned),
+            Err(_) => terr!(cmp.unknown_symbol(name)),
+        };
+        match sinfo {
+            SymInfo {
                 typ: sym::Typ::Address(_),
                 ..
-            } => Ok(sinfo.to_owned()),
-            other => temg!("Not a function address {}: {:?}", name, other),
+            } => {
+                let sinfo = sinfo.to_owned();
+                te!(cmp.check_visible(name, &sinfo));
+                Ok(sinfo)
+            }
+            other => {
+                ldebug!("Not a function address {}: {:?}", name, other);
+                let diagnostic = Diagnostic::error("not-a-def", format!("{name} is not a def"))
+                    .with_note("only defs can be invoked by name");
+                terr!(cmp.at(diagnostic, At::Text(name), "invoked here"))
+            }
         }
     }
 }
@@ -6,14 +6,129 @@ pub struct Info {
     pub number_of_imported_instructions: usize,
 }
 
-pub fn link_modules<C: Borrow<Compiler>>(mods: impl IntoIterator<Item = C>) -> Compiler {
+/// External references of a module (`extern def name;`).
+///
+/// `externs` holds the declared names; an extern address symbol refers to
+/// its name by index in there. Each site is `(instr, extern_id)`: an
+/// instruction whose address operand is a placeholder, to be patched
+/// with the address of `externs[extern_id]` once that is known.
+#[derive(Default, Debug, Clone)]
+pub struct Relocs {
+    pub externs: Vec<String>,
+    pub sites: Vec<(usize, usize)>,
+}
+
+impl Relocs {
+    /// The id of the given extern name, adding it if it is not there.
+    pub fn extern_id(&mut self, name: &str) -> usize {
+        match self.externs.iter().position(|n| n == name) {
+            Some(id) => id,
+            None => {
+                self.externs.push(name.to_owned());
+                self.externs.len() - 1
+            }
+        }
+    }
+
+    /// Names referenced by sites that are still unresolved.
+    pub fn unresolved(&self) -> impl Iterator<Item = &str> {
+        let mut ids: Vec<usize> = self.sites.iter().map(|&(_, id)| id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter().map(|id| self.externs[id].as_str())
+    }
+}
+
+/// Global defs visible to other modules: the `pub def`s.
+///
+/// Before `pub def`, defs were private by naming them with a leading `_`
+/// (past any namespace prefix, as in `ns::_name`). For the time libraries
+/// move to `pub def`, such names stay private even when `pub`.
+pub fn exports(cmp: &Compiler) -> impl Iterator<Item = (&str, usize)> {
+    cmp.global_scope_opt()
+        .into_iter()
+        .flat_map(|scope| scope.symbols())
+        .filter_map(|(name, sym_id)| {
+            let addr = sym_id.sym_info().as_addr_ref().ok()?;
+            let exported = addr.linkage == sym::Linkage::Public && !is_private_name(name);
+            exported.then_some((name, addr.addr))
+        })
+}
+
+fn is_private_name(name: &str) -> bool {
+    name.rsplit("::").next().unwrap_or(name).starts_with('_')
+}
+
+/// Link modules into a library.
+///
+/// Only the exports of each module are imported as global defs, so the
+/// library does not expose the helpers of its modules.
+pub fn link_modules<C: Borrow<Compiler>>(mods: impl IntoIterator<Item = C>) -> Result<Compiler> {
     let mut cmp = Compiler::new();
     cmp.enter_scope();
     cmp.enter_scope();
-    mods.into_iter().fold(cmp, |mut cmp, module| {
+    let mut cmp = mods.into_iter().fold(cmp, |mut cmp, module| {
         import(&mut cmp, module.borrow());
         cmp
-    })
+    });
+    te!(resolve(&mut cmp));
+    Ok(cmp)
+}
+
+/// Patch every relocation site whose extern name is now defined as a
+/// global def. Unresolved sites are kept, so that a partially linked
+/// module can be linked again later.
+///
+/// Return the number of patched sites, or fail if a site is not an
+/// instruction taking a def address, as in a corrupt object.
+pub fn resolve<T: BorrowMut<Compiler>>(mut target: T) -> Result<usize> {
+    let Compiler {
+        icode,
+        sym_table,
+        relocs,
+        ..
+    } = target.borrow_mut();
+
+    let lookup = |name: &str| -> Option<usize> {
+        let sym_id = sym_table.global_scope_opt()?.lookup_by_name(name)?;
+        let info = sym_id.sym_info();
+        if info.is_extern() {
+            return None;
+        }
+        info.addr().ok()
+    };
+
+    let Relocs { externs, sites } = relocs;
+    let mut pending = Vec::with_capacity(sites.len());
+    for &(instr, extern_id) in sites.iter() {
+        let name = te!(
+            externs.get(extern_id),
+            "Relocation site {instr} refers to no extern: {extern_id}"
+        );
+        let Some(addr) = lookup(name) else {
+            pending.push((instr, extern_id));
+            continue;
+        };
+        match icode.instructions.get_mut(instr) {
+            Some(vm::Instr::PushFuncAddr(a) | vm::Instr::RetFuncAddr(a)) => *a = addr,
+            other => temg!(
+                "Relocation site {instr} for {name} is not a func-addr instruction: {:?}",
+                other
+            ),
+        }
+    }
+    let resolved = sites.len() - pending.len();
+    *sites = pending;
+    Ok(resolved)
+}
+
+/// Fail if there are relocation sites left unresolved.
+pub fn ensure_resolved<C: Borrow<Compiler>>(cmp: C) -> Result<()> {
+    let names: Vec<&str> = cmp.borrow().relocs.unresolved().collect();
+    if !names.is_empty() {
+        temg!("Unresolved external references: {}", names.join(", "))
+    }
+    Ok(())
 }
 
 pub fn import<T, S>(mut target: T, source: &S) -> Info
@@ -27,9 +142,15 @@ where
     let instrs = target.num_instrs();
     // ID->String hashmap on source. Used later.
     let sstrings: HashMap<usize, &str> = source.strings();
+    // Exported def names on source. Used later.
+    let exported: HashMap<&str, usize> = exports(source).collect();
 
     let Compiler {
-        icode, sym_table, ..
+        icode,
+        sym_table,
+        relocs,
+        debug_info,
+        ..
     } = target.borrow_mut();
     let vm::ICode {
         instructions,
@@ -106,7 +227,9 @@ where
     // ---- Import global address symbols (def-s) ----
     // -----------------------------------------------
     //
-    // Insert symbols, only if they are global and refer to func-addr.
+    // Insert symbols, only if they are exported (see [exports]). They stay
+    // exported from the target, as `pub`. An extern of the same name, as
+    // declared by a module defining it too, is not the export.
     //
     // Translate the function address in the process.
     //
@@ -115,13 +238,34 @@ where
         .expect("global scope in source compiler")
         .symbols()
     {
-        let info = sym_id.sym_info();
-
-        if let Ok(sym::Address { addr, ret_t }) = info.as_addr_ref() {
-            sym_table.new_address(name, translate_addr(*addr), ret_t);
+        if !exported.contains_key(name) || sym_id.sym_info().is_extern() {
+            continue;
+        }
+        if let Ok(sym::Address {
+            addr,
+            ret_t,
+            params,
+            ..
+        }) = sym_id.sym_info().as_addr_ref()
+        {
+            sym_table.new_public_address(name, translate_addr(*addr), ret_t, *params);
         }
     }
 
+    // ---------------------------------
+    // ---- Importing relocations ----
+    // ---------------------------------
+    //
+    // Pending sites are carried over, with their instruction translated
+    // and their extern name re-interned in the target.
+    //
+    for &(instr, extern_id) in &source.relocs.sites {
+        let extern_id = relocs.extern_id(&source.relocs.externs[extern_id]);
+        relocs.sites.push((translate_addr(instr), extern_id));
+    }
+
+    debug_info.append(&source.debug_info, instrs);
+
     Info {
         number_of_imported_strings,
         number_of_imported_instructions,
@@ -0,0 +1,230 @@
+//! Warnings about code that compiles, but is likely a mistake.
+//!
+//! Each lint is named after the code of its warnings. A lint can be
+//! allowed (silenced) by its code, and all of them by `warnings`. Denied
+//! warnings are reported as errors.
+use {
+    super::{
+        te, temg, At, Compiler, Diagnostic, DiagnosticExt, HashMap, Mut, RecoveryExt, Result,
+        ScopesRef, SymInfo, SymbolTableExt,
+    },
+    error::diagnostic::{Label, Severity},
+    std::{collections::HashSet, ops::Range},
+};
+
+/// A `let` or `src` binding that is never used.
+pub const UNUSED_BINDING: &str = "unused-binding";
+/// A def replacing a def of another file, like an included one.
+pub const DEF_SHADOWS_INCLUDE: &str = "def-shadows-include";
+/// A cwd, environment or redirection with no effect.
+pub const IGNORED_SETTING: &str = "ignored-setting";
+/// A call with more arguments than its def uses.
+pub const SURPLUS_ARGS: &str = "surplus-args";
+
+pub const LINTS: &[&str] = &[
+    UNUSED_BINDING,
+    DEF_SHADOWS_INCLUDE,
+    IGNORED_SETTING,
+    SURPLUS_ARGS,
+];
+
+/// All lints together.
+const WARNINGS: &str = "warnings";
+
+#[derive(Default, Debug, Clone)]
+pub struct Lints {
+    allowed: HashSet<String>,
+    warned: HashSet<String>,
+    allowed_all: bool,
+    /// Report warnings as errors.
+    pub deny: bool,
+}
+
+impl Lints {
+    pub fn allow(&mut self, code: &str) -> Result<()> {
+        if code == WARNINGS {
+            self.allowed_all = true;
+            self.warned.clear();
+        } else {
+            let code = te!(known_lint(code));
+            self.warned.remove(code);
+            self.allowed.insert(code.to_owned());
+        }
+        Ok(())
+    }
+
+    pub fn warn(&mut self, code: &str) -> Result<()> {
+        if code == WARNINGS {
+            self.allowed_all = false;
+            self.allowed.clear();
+        } else {
+            let code = te!(known_lint(code));
+            self.allowed.remove(code);
+            self.warned.insert(code.to_owned());
+        }
+        Ok(())
+    }
+
+    pub fn is_allowed(&self, code: &str) -> bool {
+        self.allowed.contains(code) || self.allowed_all && !self.warned.contains(code)
+    }
+}
+
+fn known_lint(code: &str) -> Result<&str> {
+    if !LINTS.contains(&code) {
+        temg!("Unknown lint: {code} (expected {WARNINGS} or one of {LINTS:?})")
+    }
+    Ok(code)
+}
+
+/// What the lints keep track of during compilation. The uses of bindings
+/// also tell which src jobs are shared.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct Usage {
+    bindings: Vec<Binding>,
+    /// The file index (in [vm::DebugInfo::files]) of every def, by
+    /// address, and where it is named.
+    defs: HashMap<usize, (usize, Option<Label>)>,
+}
+
+impl Usage {
+    /// The file index of the def at `addr`, if known.
+    pub(crate) fn def_file(&self, addr: usize) -> Option<usize> {
+        self.defs.get(&addr).map(|&(file, _)| file)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    name: String,
+    scope_id: usize,
+    uses: usize,
+    label: Option<Label>,
+    /// The code of the binding item.
+    code: Range<usize>,
+}
+
+pub trait LintExt: Mut<Compiler> {
+    /// Report `diagnostic`, a warning, unless its lint is allowed.
+    fn warn(&mut self, mut diagnostic: Diagnostic) -> Result<()> {
+        let cmp = self.borrow_mut();
+        if cmp.lints.is_allowed(diagnostic.code) {
+            return Ok(());
+        }
+        if cmp.lints.deny {
+            diagnostic.severity = Severity::Error;
+            diagnostic.notes.push("warnings are denied".to_owned());
+        }
+        cmp.report(diagnostic)
+    }
+
+    /// Track the uses of `name`, just bound by the `let` or `src` item
+    /// compiled to `code`.
+    fn bind(&mut self, name: &str, code: Range<usize>) {
+        let cmp = self.borrow_mut();
+        let binding = Binding {
+            name: name.to_owned(),
+            scope_id: cmp.current_scope_id(),
+            uses: 0,
+            label: cmp.label(At::Text(name), "bound here"),
+            code,
+        };
+        cmp.usage.bindings.push(binding);
+    }
+
+    /// Note the use of the variable `name`, found as `sinfo`.
+    fn use_variable(&mut self, name: &str, sinfo: &SymInfo) {
+        let bindings = &mut self.borrow_mut().usage.bindings;
+        let binding = bindings
+            .iter_mut()
+            .rev()
+            .find(|binding| binding.name == name && binding.scope_id == sinfo.scope_id);
+        if let Some(binding) = binding {
+            binding.uses += 1;
+        }
+    }
+
+    /// Share the src jobs used more than once among their uses, instead of
+    /// piping them to their only reader.
+    fn share_reused_sources(&mut self) {
+        let cmp = self.borrow_mut();
+        for Binding { uses, code, .. } in &cmp.usage.bindings {
+            if *uses < 2 {
+                continue;
+            }
+            for instr in cmp.icode.instructions.range_mut(code.clone()) {
+                if let &mut vm::Instr::Pipe(fp_off) = instr {
+                    *instr = vm::Instr::Share {
+                        fp_off,
+                        readers: *uses,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Warn about the bindings never used. Names starting with `_` are
+    /// meant to be unused.
+    fn warn_unused_bindings(&mut self) -> Result<()> {
+        let bindings = std::mem::take(&mut self.borrow_mut().usage.bindings);
+        for Binding {
+            name, uses, label, ..
+        } in bindings
+        {
+            if uses > 0 || name.starts_with('_') {
+                continue;
+            }
+            let mut diagnostic =
+                Diagnostic::warning(UNUSED_BINDING, format!("{name} is never used"))
+                    .with_note(format!("name it _{name} if this is on purpose"));
+            diagnostic.primary = label;
+            te!(self.warn(diagnostic));
+        }
+        Ok(())
+    }
+
+    /// Warn if the def `name`, about to be defined in the current source,
+    /// replaces one defined in another source.
+    fn check_def_shadowing(&mut self, name: &str) -> Result<()> {
+        let cmp = self.borrow_mut();
+        let (Some(source), Ok(prev)) = (cmp.sources.last(), cmp.lookup(name)) else {
+            return Ok(());
+        };
+        let (Ok(prev_addr), true) = (prev.as_addr_ref(), prev.scope_id == cmp.current_scope_id())
+        else {
+            return Ok(());
+        };
+        let Some((file, prev_label)) = cmp.usage.defs.get(&prev_addr.addr) else {
+            return Ok(());
+        };
+        if *file == source.file {
+            return Ok(());
+        }
+
+        let prev_file = &cmp.debug_info.files[*file];
+        let diagnostic = Diagnostic::warning(
+            DEF_SHADOWS_INCLUDE,
+            format!("{name} replaces the def of {prev_file}"),
+        )
+        .with_secondary(prev_label.to_owned().map(|label| Label {
+            message: "replaced def".to_owned(),
+            ..label
+        }));
+        let diagnostic = cmp.at(diagnostic, At::Text(name), "defined here");
+        te!(cmp.warn(diagnostic));
+        Ok(())
+    }
+
+    /// Remember where the def `name` at `addr` is defined (see
+    /// [Usage::def_file]).
+    fn record_def_source(&mut self, addr: usize, name: &str) {
+        let cmp = self.borrow_mut();
+        let Some(file) = cmp.sources.last().map(|source| source.file) else {
+            return;
+        };
+        let label = cmp.label(At::Text(name), "");
+        cmp.usage.defs.insert(addr, (file, label));
+    }
+}
+
+impl<C: Mut<Compiler>> LintExt for C {}
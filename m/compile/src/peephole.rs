@@ -0,0 +1,239 @@
+//! Peephole optimization.
+//!
+//! The body of a def is straight-line code: a call returns to the next
+//! instruction, and the only jumps skip over nested defs (see [prune]).
+//! Every push takes the next cell of the frame, so the frame offset of a
+//! cell is the number of pushes before it in the body. A cell pushed from a
+//! constant keeps it: calls only return into cells pushed as `PushNull`.
+//!
+//! - Copies of a constant cell (`PushLocal`, `RetLocal`) are folded into
+//!   pushes (returns) of the constant itself, and its clean-ups, which do
+//!   nothing to constants, are dropped.
+//! - A call to a def that only returns a constant is dropped, and its
+//!   return cell becomes that constant. Every use of the cell is folded, so
+//!   nothing reads the cell the call no longer fills.
+//! - Jumps to the next instruction are dropped.
+//! - Consecutive `Allocate`s are merged, and empty ones dropped.
+//!
+//! No push is added or dropped, so frame layouts stay the same, and no
+//! instruction a call or a jump lands on is dropped.
+use {super::*, std::collections::HashSet};
+
+pub struct Info {
+    pub number_of_folded_instructions: usize,
+    pub number_of_dropped_instructions: usize,
+}
+
+/// Optimize the code of `cmp` in place.
+pub fn optimize(cmp: &mut Compiler) -> Info {
+    let reloc_sites: HashSet<usize> = cmp.relocs.sites.iter().map(|&(instr, _)| instr).collect();
+    let instructions = &mut cmp.icode.instructions;
+    let len = instructions.len();
+
+    // ---- Labels: what calls and jumps land on ----
+    let mut labels = HashSet::new();
+    for (idx, instr) in instructions.iter().enumerate() {
+        match *instr {
+            i::Jump { addr } => {
+                labels.insert(addr);
+                // The entry of the skipped def
+                labels.insert(idx + 1);
+            }
+            i::PushFuncAddr(addr) | i::RetFuncAddr(addr) if !reloc_sites.contains(&idx) => {
+                labels.insert(addr);
+            }
+            _ => (),
+        }
+    }
+
+    // ---- Constant folding, body by body ----
+    //
+    // Folding a body can make its def constant, and fold the calls to it in
+    // the bodies before it: go on until nothing changes.
+    let bodies: Vec<usize> = iter::once(0)
+        .chain(instructions.iter().enumerate().filter_map(|(idx, instr)| {
+            matches!(*instr, i::Jump { addr } if addr > idx + 1).then_some(idx + 1)
+        }))
+        .collect();
+    let mut keep = vec![true; len];
+    let mut number_of_folded_instructions = 0;
+    loop {
+        let constants: HashMap<usize, i> = bodies
+            .iter()
+            .filter_map(|&addr| Some((addr, constant_def(instructions, &keep, addr)?)))
+            .collect();
+        let mut body = Body {
+            instructions,
+            keep: &mut keep,
+            reloc_sites: &reloc_sites,
+            constants: &constants,
+        };
+        let folded: usize = bodies.iter().map(|&start| body.fold(start)).sum();
+        if folded == 0 {
+            break;
+        }
+        number_of_folded_instructions += folded;
+    }
+
+    // ---- Dropping ----
+    let mut allocate = None;
+    for idx in 0..len {
+        if !keep[idx] {
+            continue;
+        }
+        let droppable = !labels.contains(&idx);
+        match instructions[idx] {
+            i::Jump { addr } if addr == idx + 1 && droppable => keep[idx] = false,
+            i::Allocate { size } if droppable => match allocate {
+                Some(prev) => {
+                    if let i::Allocate { size: total } = &mut instructions[prev] {
+                        *total += size;
+                    }
+                    keep[idx] = false;
+                }
+                None if size == 0 => keep[idx] = false,
+                None => allocate = Some(idx),
+            },
+            i::Allocate { .. } => allocate = Some(idx),
+            _ => allocate = None,
+        }
+    }
+
+    let rewrite::Info {
+        number_of_dropped_instructions,
+        ..
+    } = rewrite::retain(cmp, keep, &HashSet::new());
+
+    Info {
+        number_of_folded_instructions,
+        number_of_dropped_instructions,
+    }
+}
+
+/// The constant a def at `addr` returns, as the instruction pushing it, if
+/// its body does nothing else than pushing cells.
+fn constant_def(instructions: &vm::Instrs, keep: &[bool], addr: usize) -> Option<i> {
+    let mut body = (addr..instructions.len())
+        .filter(|&idx| keep[idx])
+        .map(|idx| instructions[idx]);
+    let i::Allocate { .. } = body.next()? else {
+        return None;
+    };
+    loop {
+        match body.next()? {
+            i::PushNull | i::PushStr(_) | i::PushNat(_) | i::PushSysCall(_) => (),
+            i::RetStr(id) => break Some(i::PushStr(id)),
+            i::RetNat(val) => break Some(i::PushNat(val)),
+            _ => break None,
+        }
+    }
+}
+
+struct Body<'a> {
+    instructions: &'a mut vm::Instrs,
+    keep: &'a mut [bool],
+    reloc_sites: &'a HashSet<usize>,
+    /// The constant defs, by address (see [constant_def]).
+    constants: &'a HashMap<usize, i>,
+}
+
+impl Body<'_> {
+    /// Fold the uses of constant cells in the body starting at `start`,
+    /// returning how many instructions were folded.
+    fn fold(&mut self, start: usize) -> usize {
+        let mut cells: Vec<Option<i>> = vec![];
+        let mut folded = 0;
+        let mut idx = start;
+        while idx < self.instructions.len() {
+            let instr = self.instructions[idx];
+            let cell = |fp_off: usize| cells.get(fp_off).copied().flatten();
+            let pushed = match instr {
+                _ if !self.keep[idx] => None,
+                // A nested def
+                i::Jump { addr } if addr > idx + 1 => {
+                    idx = addr;
+                    continue;
+                }
+                i::Return(_) => break,
+                i::PushStr(_) | i::PushNat(_) | i::PushSysCall(_) => Some(Some(instr)),
+                // Extern placeholders are patched by the linker
+                i::PushFuncAddr(_) if !self.reloc_sites.contains(&idx) => Some(Some(instr)),
+                i::PushFuncAddr(_) | i::PushNull | i::PushArgs => Some(None),
+                i::PushLocal(fp_off) => {
+                    let constant = cell(fp_off);
+                    if let Some(constant) = constant {
+                        self.instructions[idx] = constant;
+                        folded += 1;
+                    }
+                    Some(constant)
+                }
+                i::RetLocal(fp_off) => {
+                    let ret = match cell(fp_off) {
+                        Some(i::PushStr(id)) => Some(i::RetStr(id)),
+                        Some(i::PushNat(val)) => Some(i::RetNat(val)),
+                        Some(i::PushFuncAddr(addr)) => Some(i::RetFuncAddr(addr)),
+                        _ => None,
+                    };
+                    if let Some(ret) = ret {
+                        self.instructions[idx] = ret;
+                        // Returning a local takes it
+                        cells[fp_off] = Some(i::PushNull);
+                        folded += 1;
+                    }
+                    None
+                }
+                i::CleanUp(fp_off)
+                | i::Collect(fp_off)
+                | i::Pipe(fp_off)
+                | i::Share { fp_off, .. }
+                | i::BufferString(fp_off) => {
+                    if cell(fp_off).is_some() {
+                        self.keep[idx] = false;
+                        folded += 1;
+                    }
+                    None
+                }
+                i::Call(_) => {
+                    if let Some((ret, constant)) = self.constant_call(&cells) {
+                        cells[ret] = Some(constant);
+                        self.keep[idx] = false;
+                        folded += 1;
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(pushed) = pushed {
+                cells.push(pushed);
+            }
+            idx += 1;
+        }
+        folded
+    }
+
+    /// The return cell of a call to a constant def, with the frame in
+    /// `cells`, and the constant it returns.
+    ///
+    /// Going down from the top, a call frame holds the number of arguments,
+    /// the arguments, the cwd, the target, the number of inputs, the inputs,
+    /// the number of environments, their name-value pairs, and the return
+    /// cell (see [vm::Vm::ret_cell_addr]).
+    fn constant_call(&self, cells: &[Option<i>]) -> Option<(usize, i)> {
+        let below = |off: usize| match cells.len().checked_sub(1 + off).map(|idx| cells[idx]) {
+            Some(Some(i::PushNat(n))) => Some(n),
+            _ => None,
+        };
+        let nargs = below(0)?;
+        let target = cells.len().checked_sub(1 + nargs + 2)?;
+        let Some(i::PushFuncAddr(addr)) = cells[target] else {
+            return None;
+        };
+        let constant = *self.constants.get(&addr)?;
+        let ninps = below(nargs + 3)?;
+        let nenvs = below(nargs + 3 + ninps + 1)?;
+        let ret = cells
+            .len()
+            .checked_sub(1 + nargs + 3 + ninps + 1 + 2 * nenvs + 1)?;
+        Some((ret, constant))
+    }
+}
@@ -0,0 +1,136 @@
+//! Dead-def elimination.
+//!
+//! A def occupies `[addr - 1, end)`: the `Jump` over its body, followed by
+//! the body itself, up to the jump target. Defs nest (all defs of a script
+//! live inside `m___system_main___`), and every instruction belongs to its
+//! innermost def, or to the top level.
+//!
+//! Defs are only referred to by `PushFuncAddr`/`RetFuncAddr` (a `Call`
+//! goes through a pushed address, and a `Jump` only skips over a def
+//! body), so a def is live when a live instruction refers to it.
+use {super::*, std::collections::HashSet};
+
+pub enum Roots {
+    /// The top-level code, which calls into `m___system_main___`.
+    Entry,
+    /// The exported defs (see [link::exports]). The top-level code is dropped,
+    /// so the result can only be called into: running it does nothing.
+    Exports,
+}
+
+pub struct Info {
+    pub number_of_dropped_defs: usize,
+    pub number_of_dropped_instructions: usize,
+    pub number_of_dropped_strings: usize,
+}
+
+/// Drop every def not reachable from `roots`, along with the strings
+/// only they use.
+pub fn prune(cmp: &mut Compiler, roots: Roots) -> Info {
+    let instructions = &cmp.icode.instructions;
+    let len = instructions.len();
+    let reloc_sites: HashSet<usize> = cmp.relocs.sites.iter().map(|&(instr, _)| instr).collect();
+
+    // ---- Def regions, outer before inner ----
+    //
+    // Found from the code rather than the symbols, which do not name every
+    // def (for example, the non-exported defs of a linked module).
+    let defs: Vec<(usize, usize)> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, instr)| match *instr {
+            i::Jump { addr: end } if end > idx + 1 => Some((idx + 1, end.min(len))),
+            _ => None,
+        })
+        .collect();
+
+    // ---- Innermost def of each instruction ----
+    let mut owner: Vec<Option<usize>> = vec![None; len];
+    for (def, &(addr, end)) in defs.iter().enumerate() {
+        for o in &mut owner[addr - 1..end] {
+            *o = Some(def);
+        }
+    }
+    let def_at: HashMap<usize, usize> = defs
+        .iter()
+        .enumerate()
+        .map(|(def, &(addr, _))| (addr, def))
+        .collect();
+
+    // ---- Reachability ----
+    let mut live = vec![false; defs.len()];
+    let mut work: Vec<Option<usize>> = vec![];
+    let mut mark = |addr: usize, work: &mut Vec<Option<usize>>| {
+        if let Some(&def) = def_at.get(&addr) {
+            if !live[def] {
+                live[def] = true;
+                work.push(Some(def));
+            }
+        }
+    };
+    match roots {
+        Roots::Entry => work.push(None),
+        Roots::Exports => {
+            for (_, addr) in link::exports(cmp) {
+                mark(addr, &mut work);
+            }
+        }
+    }
+    while let Some(region) = work.pop() {
+        let range = match region {
+            None => 0..len,
+            Some(def) => defs[def].0 - 1..defs[def].1,
+        };
+        for idx in range {
+            if owner[idx] != region || reloc_sites.contains(&idx) {
+                continue;
+            }
+            if let i::PushFuncAddr(addr) | i::RetFuncAddr(addr) = instructions[idx] {
+                mark(addr, &mut work);
+            }
+        }
+    }
+
+    // ---- Rewrite ----
+    let top_live = matches!(roots, Roots::Entry);
+    let mut keep: Vec<bool> = owner
+        .iter()
+        .map(|o| o.map_or(top_live, |def| live[def]))
+        .collect();
+    // A dropped top level is left empty rather than missing, for the defs
+    // to stay well-formed code: only its `Allocate` and its last `Return`
+    // are kept, around the defs
+    let empty_top = !top_live
+        && len > 1
+        && owner[0].is_none()
+        && owner[len - 1].is_none()
+        && matches!(instructions[0], i::Allocate { .. })
+        && matches!(instructions[len - 1], i::Return(_));
+    if empty_top {
+        keep[0] = true;
+        keep[len - 1] = true;
+    }
+    let dropped_defs: HashSet<usize> = defs
+        .iter()
+        .zip(&live)
+        .filter(|&(_, &live)| !live)
+        .map(|(&(addr, _), _)| addr)
+        .collect();
+
+    let rewrite::Info {
+        number_of_dropped_instructions,
+        number_of_dropped_strings,
+    } = rewrite::retain(cmp, keep, &dropped_defs);
+    if empty_top {
+        let instructions = &mut cmp.icode.instructions;
+        instructions[0] = i::Allocate { size: 0 };
+        let last = instructions.len() - 1;
+        instructions[last] = i::Return(0);
+    }
+
+    Info {
+        number_of_dropped_defs: dropped_defs.len(),
+        number_of_dropped_instructions,
+        number_of_dropped_strings,
+    }
+}
@@ -0,0 +1,106 @@
+//! Compiling past errors.
+//!
+//! A failed item is reported, and the compilation goes on with the next
+//! one. The name the item would define is poisoned: not finding it later
+//! is a consequence of the reported failure, so it is not reported again.
+//! A failed include poisons every name.
+//!
+//! The reported diagnostics fail the compilation at its end (see
+//! [RecoveryExt::check_errors]), or as soon as there are
+//! [Compiler::max_errors] of them.
+use {
+    super::{
+        mem, te, terr, At, CheckExt, Compiler, Diagnostic, DiagnosticExt, Error, ErrorKind, Mut,
+        Result,
+    },
+    error::diagnostic::Severity,
+    std::collections::HashSet,
+};
+
+/// The names whose definitions failed.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct Poison {
+    names: HashSet<String>,
+    everything: bool,
+}
+
+impl Poison {
+    pub fn contains(&self, name: &str) -> bool {
+        self.everything || self.names.contains(name)
+    }
+}
+
+/// The error of using a poisoned name. It is dropped when recovering from
+/// the item that used it.
+#[derive(Debug)]
+pub struct Poisoned;
+
+pub trait RecoveryExt: Mut<Compiler> {
+    /// Report the failure of an item, which would define `name`, and carry
+    /// on. Failures that are not about the source (like I/O errors) are
+    /// returned.
+    fn recover(&mut self, err: Error, name: Option<&str>) -> Result<()> {
+        let cmp = self.borrow_mut();
+        if let Some(name) = name {
+            cmp.poison.names.insert(name.to_owned());
+        }
+        cmp.forget_params();
+        match cmp.diagnose(err, At::Span(<_>::default())) {
+            Error {
+                kind: ErrorKind::Diagnostic(diagnostic),
+                ..
+            } => cmp.report(*diagnostic),
+            Error {
+                kind: ErrorKind::Poisoned(_),
+                ..
+            } => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    /// Add `diagnostic` to the ones failing the compilation.
+    fn report(&mut self, diagnostic: Diagnostic) -> Result<()> {
+        let cmp = self.borrow_mut();
+        cmp.diagnostics.push(diagnostic);
+
+        let errors = error_count(cmp);
+        if cmp.max_errors != 0 && errors >= cmp.max_errors {
+            let last = cmp.diagnostics.last_mut().unwrap();
+            last.notes.push(format!("stopping after {errors} errors"));
+            terr!(mem::take(&mut cmp.diagnostics))
+        }
+        Ok(())
+    }
+
+    /// Report the syntax errors in `err`, which come from parsing the
+    /// current source.
+    fn report_syntax_errors(&mut self, err: parse::Error) -> Result<()> {
+        let diagnostics = te!(self.borrow().syntax_errors(err));
+        for diagnostic in diagnostics {
+            te!(self.report(diagnostic));
+        }
+        Ok(())
+    }
+
+    /// Fail with all the reported diagnostics, if any of them is an error.
+    fn check_errors(&mut self) -> Result<()> {
+        let cmp = self.borrow_mut();
+        if error_count(cmp) > 0 {
+            terr!(mem::take(&mut cmp.diagnostics))
+        }
+        Ok(())
+    }
+
+    fn poison_everything(&mut self) {
+        self.borrow_mut().poison.everything = true;
+    }
+}
+
+impl<C: Mut<Compiler>> RecoveryExt for C {}
+
+fn error_count(cmp: &Compiler) -> usize {
+    cmp.diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count()
+}
@@ -0,0 +1,172 @@
+//! Rewriting a compiled module in place: dropping instructions and
+//! strings, and re-pointing everything that refers to them.
+use {
+    super::{i, mem, sym, Compiler, SymInfo},
+    crate::symbol_table::ScopesExt,
+    std::collections::HashSet,
+};
+
+pub struct Info {
+    pub number_of_dropped_instructions: usize,
+    pub number_of_dropped_strings: usize,
+}
+
+/// Old-to-new index translation, for a subset of kept items.
+pub(crate) struct Remap {
+    pos: Vec<usize>,
+    kept: Vec<bool>,
+}
+
+impl Remap {
+    pub fn new(kept: Vec<bool>) -> Self {
+        let mut pos = Vec::with_capacity(kept.len() + 1);
+        let mut next = 0;
+        for &k in &kept {
+            pos.push(next);
+            if k {
+                next += 1;
+            }
+        }
+        pos.push(next);
+        Self { pos, kept }
+    }
+
+    /// The new index of a kept item.
+    pub fn get(&self, old: usize) -> Option<usize> {
+        if *self.kept.get(old)? {
+            Some(self.pos[old])
+        } else {
+            None
+        }
+    }
+
+    /// The new position of `old`: for dropped items, that is the position
+    /// of the first kept item after them (or the end).
+    pub fn pos(&self, old: usize) -> usize {
+        self.pos[old.min(self.kept.len())]
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.kept.len() - self.pos[self.kept.len()]
+    }
+}
+
+/// Drop the instructions not marked in `keep`, and every string that no
+/// kept instruction refers to.
+///
+/// Jump targets, func-addrs, relocation sites, source locations and
+/// symbols are translated to the new layout. Symbols of the defs starting at `dropped_defs`, and
+/// symbols referring to dropped strings, are removed from the table.
+pub(crate) fn retain(cmp: &mut Compiler, keep: Vec<bool>, dropped_defs: &HashSet<usize>) -> Info {
+    let Compiler {
+        icode,
+        sym_table,
+        relocs,
+        debug_info,
+        ..
+    } = cmp;
+
+    let instrs = Remap::new(keep);
+    let reloc_sites: HashSet<usize> = relocs.sites.iter().map(|&(instr, _)| instr).collect();
+
+    let mut used = vec![false; icode.strings.len()];
+    for (idx, instr) in icode.instructions.iter().enumerate() {
+        if let (Some(_), &(i::PushStr(id) | i::RetStr(id))) = (instrs.get(idx), instr) {
+            used[id] = true;
+        }
+    }
+    let strs = Remap::new(used);
+
+    let translate_instr = |idx: usize, instr: &i| -> i {
+        let str_id = |id| strs.get(id).expect("string of a kept instruction");
+        match *instr {
+            i::PushStr(id) => i::PushStr(str_id(id)),
+            i::RetStr(id) => i::RetStr(str_id(id)),
+            // Extern placeholders are patched by the linker, not translated
+            i::PushFuncAddr(addr) if !reloc_sites.contains(&idx) => {
+                i::PushFuncAddr(instrs.pos(addr))
+            }
+            i::RetFuncAddr(addr) if !reloc_sites.contains(&idx) => i::RetFuncAddr(instrs.pos(addr)),
+            i::Jump { addr } => i::Jump {
+                addr: instrs.pos(addr),
+            },
+            other => other,
+        }
+    };
+
+    icode.instructions = mem::take(&mut icode.instructions)
+        .iter()
+        .enumerate()
+        .filter(|&(idx, _)| instrs.get(idx).is_some())
+        .map(|(idx, instr)| translate_instr(idx, instr))
+        .collect();
+
+    icode.strings = mem::take(&mut icode.strings)
+        .into_iter()
+        .filter_map(|(s, vm::StringInfo { id })| Some((s, vm::StringInfo { id: strs.get(id)? })))
+        .collect();
+
+    relocs.sites = mem::take(&mut relocs.sites)
+        .into_iter()
+        .filter_map(|(instr, extern_id)| Some((instrs.get(instr)?, extern_id)))
+        .collect();
+
+    // The location of a dropped instruction goes on to the next kept one,
+    // unless that one has its own
+    let len = icode.instructions.len();
+    for location in mem::take(&mut debug_info.locations) {
+        let instr = instrs.pos(location.instr);
+        if instr < len {
+            debug_info.add_location(vm::debug_info::Location { instr, ..location });
+        }
+    }
+    debug_info.defs = mem::take(&mut debug_info.defs)
+        .into_iter()
+        .filter_map(|(addr, name)| Some((instrs.get(addr)?, name)))
+        .collect();
+
+    sym_table.retain_symbols(|_, info| translate_info(info, &instrs, &strs, dropped_defs));
+
+    Info {
+        number_of_dropped_instructions: instrs.dropped(),
+        number_of_dropped_strings: strs.dropped(),
+    }
+}
+
+/// Translate addresses and string ids in `info`, returning false if it
+/// refers to something dropped.
+fn translate_info(
+    info: &mut SymInfo,
+    instrs: &Remap,
+    strs: &Remap,
+    dropped_defs: &HashSet<usize>,
+) -> bool {
+    let translate = |info: &mut SymInfo| translate_info(info, instrs, strs, dropped_defs);
+    match &mut info.typ {
+        sym::Typ::Address(sym::Address {
+            addr,
+            ret_t,
+            linkage: sym::Linkage::Internal | sym::Linkage::Public,
+            ..
+        }) => {
+            if dropped_defs.contains(addr) {
+                return false;
+            }
+            *addr = instrs.pos(*addr);
+            translate(ret_t)
+        }
+        sym::Typ::Address(sym::Address { ret_t, .. }) => translate(ret_t),
+        sym::Typ::Literal(sym::Literal {
+            id,
+            lit_type: sym::LitType::String,
+        }) => match strs.get(*id) {
+            Some(new_id) => {
+                *id = new_id;
+                true
+            }
+            None => false,
+        },
+        sym::Typ::Literal(_) => true,
+        sym::Typ::Local(local) => local.types.iter_mut().all(translate),
+    }
+}
@@ -1,5 +1,6 @@
 use super::{
-    sym::{Address, LitType, Literal, Local, Typ},
+    link::Relocs,
+    sym::{Address, Linkage, LitType, Literal, Local, Params, Typ},
     te, Compiler, SymInfo,
 };
 
@@ -8,28 +9,58 @@ buf::sd_struct![SymInfo, typ, scope_id];
 buf::sd_type![Typ, Local, 0u8, Address, 1u8, Literal, 2u8];
 
 buf::sd_struct![Local, fp_off, is_alias, types];
-buf::sd_struct![Address, addr, ret_t];
+buf::sd_struct![Address, addr, ret_t, linkage, params];
+buf::sd_struct![Params, min, max];
 buf::sd_struct![Literal, lit_type, id];
 
+buf::sd_enum![Linkage, Internal, 0u8, Extern, 1u8, Public, 2u8];
+buf::sd_struct![Relocs, externs, sites];
+
 buf::sd_enum![LitType, Null, 0u8, String, 1u8, Natural, 2u8, Syscall, 3u8, Args, 4u8];
 
 buf::sd![
     Compiler,
     |Compiler {
-         icode, sym_table, ..
+         icode,
+         sym_table,
+         relocs,
+         debug_info,
+         ..
      },
-     mut dst| {
-        icode.write_to(Ok(&mut dst))?;
-        sym_table.write_out(&mut dst)?;
-        Ok(())
+     dst| {
+        // The sections, under one header and checksum
+        let mut body = vec![];
+        icode.write_to(Ok(&mut body))?;
+        sym_table.write_out(&mut body)?;
+        relocs.write_out(&mut body)?;
+        debug_info.write_out(&mut body)?;
+        vm::icode::write_object(dst, &body)
     },
-    |mut inp| {
+    |inp| {
+        let body = te!(vm::icode::read_object(inp));
+        let mut inp = body.as_slice();
         Ok(Compiler {
-            icode: te!(
-                vm::ICode::load_from(Ok(&mut inp)).map_err(|e| format!("Loading icode: {:?}", e))
-            ),
+            icode: te!(vm::ICode::load_from(Ok(&mut inp)).map_err(code_error)),
             sym_table: te!(<_>::read_in(&mut inp)),
-            current_file_path: <_>::default(),
+            relocs: te!(<_>::read_in(&mut inp)),
+            debug_info: te!(<_>::read_in(&mut inp)),
+            ..Compiler::new()
         })
     }
 ];
+
+/// An error decoding the code of an object, whose body passed its checksum.
+fn code_error(err: vm::Error) -> buf::sd2::Error {
+    let vm::Error { kind, trace } = err;
+    let msg = match kind {
+        vm::ErrorKind::BufferSd(err) => return err,
+        vm::ErrorKind::Io(err) => err.to_string(),
+        vm::ErrorKind::Message(msg) => msg,
+        vm::ErrorKind::Msg(msg) => msg.to_owned(),
+        _ => "undecodable".to_owned(),
+    };
+    buf::sd2::Error {
+        kind: buf::sd2::ErrorKind::Other(format!("Invalid object code: {msg}")),
+        trace,
+    }
+}
@@ -18,6 +18,12 @@ impl Show for Compiler {
             }
             writeln!(o, "=== SYMBOLS ===")?;
             self.sym_table.write_to_impl(&mut o)?;
+            if !self.relocs.sites.is_empty() {
+                writeln!(o, "=== RELOCATIONS ===")?;
+                for &(instr, extern_id) in &self.relocs.sites {
+                    writeln!(o, "[{:4}] {}", instr, self.relocs.externs[extern_id])?;
+                }
+            }
 
             #[cfg(feature = "funny_name_lookup")]
             writeln!(o, "=== [Funny Name Lookup]")?;
@@ -26,6 +26,29 @@ pub struct Local {
 pub struct Address {
     pub addr: usize,
     pub ret_t: Box<Info>,
+    pub linkage: Linkage,
+    pub params: Params,
+}
+/// How many arguments an [Address] takes, as far as its uses of `$args`
+/// tell. `max` is [Params::VARIADIC] when any number of them will do.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Params {
+    pub min: usize,
+    pub max: usize,
+}
+/// How an [Address] is bound.
+///
+/// An `Extern` address is a placeholder: its `addr` is the id of the
+/// external name in [crate::link::Relocs], and every use of it is
+/// recorded as a relocation site, to be patched by the linker.
+///
+/// A `Public` address is an `Internal` one declared with `pub def`, and
+/// exported to other modules (see [crate::link::exports]).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Linkage {
+    Internal,
+    Extern,
+    Public,
 }
 #[derive(Clone, Eq, PartialEq)]
 pub struct Literal {
@@ -70,6 +93,22 @@ impl Info {
         Self::typ(Typ::address(id, ret_t))
     }
 
+    pub fn extern_address(extern_id: usize) -> Self {
+        let mut si = Self::address(extern_id, &Self::NULL);
+        if let Typ::Address(addr) = &mut si.typ {
+            addr.linkage = Linkage::Extern;
+        }
+        si
+    }
+
+    pub fn public_address(id: usize, ret_t: &Self) -> Self {
+        let mut si = Self::address(id, ret_t);
+        if let Typ::Address(addr) = &mut si.typ {
+            addr.linkage = Linkage::Public;
+        }
+        si
+    }
+
     pub fn args() -> Self {
         Self::typ(Typ::args())
     }
@@ -94,6 +133,13 @@ impl Info {
         self
     }
 
+    pub fn with_params(mut self, params: Params) -> Self {
+        if let Typ::Address(addr) = &mut self.typ {
+            addr.params = params;
+        }
+        self
+    }
+
     pub fn aliased(&self) -> Self {
         let mut typ = self.to_owned();
         typ.typ = Typ::alias(typ.to_owned());
@@ -120,6 +166,33 @@ impl Info {
     pub fn addr(&self) -> Result<usize> {
         self.as_addr_ref().map(|i| i.addr)
     }
+    pub fn is_extern(&self) -> bool {
+        matches!(
+            self.as_addr_ref(),
+            Ok(Address {
+                linkage: Linkage::Extern,
+                ..
+            })
+        )
+    }
+    /// Whether this is known when compiling, like the value of a `val`: a
+    /// string or natural literal, or an array of them.
+    pub fn is_constant(&self) -> bool {
+        match &self.typ {
+            Typ::Literal(lit) => matches!(lit.lit_type, LitType::String | LitType::Natural),
+            Typ::Local(local) => local.is_alias && local.types.iter().all(Self::is_constant),
+            Typ::Address(_) => false,
+        }
+    }
+    pub fn is_public(&self) -> bool {
+        matches!(
+            self.as_addr_ref(),
+            Ok(Address {
+                linkage: Linkage::Public,
+                ..
+            })
+        )
+    }
     pub fn val(&self) -> usize {
         match self.typ {
             Typ::Address(Address { addr: v, .. })
@@ -167,6 +240,21 @@ impl Local {
     }
 }
 
+impl Params {
+    pub const VARIADIC: usize = usize::MAX;
+    /// Unknown parameters, like those of an extern def.
+    pub const ANY: Self = Self {
+        min: 0,
+        max: Self::VARIADIC,
+    };
+    /// The parameters of a def not using `$args`.
+    pub const NONE: Self = Self { min: 0, max: 0 };
+
+    pub fn accepts(&self, nargs: usize) -> bool {
+        (self.min..=self.max).contains(&nargs)
+    }
+}
+
 impl Typ {
     pub fn size(&self) -> u16 {
         match self {
@@ -221,6 +309,8 @@ impl Typ {
         Self::Address(Address {
             addr,
             ret_t: Box::new(ret_t.to_owned()),
+            linkage: Linkage::Internal,
+            params: Params::ANY,
         })
     }
 
@@ -270,9 +360,31 @@ impl fmt::Debug for Local {
 
 impl fmt::Debug for Address {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self { addr, ret_t } = self;
-        write!(f, "*{}: {:?}", addr, ret_t)?;
-        Ok(())
+        let Self {
+            addr,
+            ret_t,
+            linkage,
+            params,
+        } = self;
+        match linkage {
+            Linkage::Internal => write!(f, "*{}", addr)?,
+            Linkage::Extern => write!(f, "extern *{}", addr)?,
+            Linkage::Public => write!(f, "pub *{}", addr)?,
+        }
+        write!(f, "({}): {:?}", params, ret_t)
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self { min, max } if min == max => write!(f, "{}", min),
+            Self {
+                min,
+                max: Self::VARIADIC,
+            } => write!(f, "{}..", min),
+            Self { min, max } => write!(f, "{}..={}", min, max),
+        }
     }
 }
 
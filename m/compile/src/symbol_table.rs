@@ -34,8 +34,31 @@ where
         self.insert_to_scope(name, SymInfo::typ(sym::Typ::array(types)))
     }
 
-    fn new_address<S: Into<String>>(&mut self, name: S, addr: usize, ret_t: &SymInfo) -> SymInfo {
-        self.insert_to_scope(name, SymInfo::address(addr, ret_t))
+    fn new_address<S: Into<String>>(
+        &mut self,
+        name: S,
+        addr: usize,
+        ret_t: &SymInfo,
+        params: sym::Params,
+    ) -> SymInfo {
+        self.insert_to_scope(name, SymInfo::address(addr, ret_t).with_params(params))
+    }
+
+    fn new_public_address<S: Into<String>>(
+        &mut self,
+        name: S,
+        addr: usize,
+        ret_t: &SymInfo,
+        params: sym::Params,
+    ) -> SymInfo {
+        self.insert_to_scope(
+            name,
+            SymInfo::public_address(addr, ret_t).with_params(params),
+        )
+    }
+
+    fn new_extern_address<S: Into<String>>(&mut self, name: S, extern_id: usize) -> SymInfo {
+        self.insert_to_scope(name, SymInfo::extern_address(extern_id))
     }
 
     fn new_local<T>(&mut self, types: T, name: String) -> &mut SymInfo
@@ -209,8 +232,8 @@ pub fn scope_stack_size(scope: &(impl ScopeRef + ?Sized)) -> usize {
 /// Looks-through *all* symbols.
 pub fn find_func_name<'s>(st: &'s impl SymbolTableExt, faddr: &usize) -> Option<&'s str> {
     st.all_symbols().find_map(|(n, i)| {
-        let sym::Address { addr, .. } = i.sym_info().as_addr_ref().ok()?;
-        if addr == faddr {
+        let sym::Address { addr, linkage, .. } = i.sym_info().as_addr_ref().ok()?;
+        if addr == faddr && *linkage != sym::Linkage::Extern {
             return Some(n);
         }
         None
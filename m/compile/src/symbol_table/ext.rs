@@ -103,6 +103,13 @@ pub(crate) trait ScopesExt: scopes::ApiMut + scopes::ApiRef {
         info.to_owned()
     }
 
+    /// Rewrite or drop symbols, in all scopes.
+    fn retain_symbols(&mut self, mut keep: impl FnMut(&str, &mut SymInfo) -> bool) {
+        for scope in self.scopes_mut() {
+            scope.retain(&mut keep);
+        }
+    }
+
     fn insert_to_scope(&mut self, name: impl ToName, info: impl ToSymInfo) -> SymInfo {
         self.insert_to_scope_mut(name, info).to_owned()
     }
@@ -50,6 +50,23 @@ pub(crate) trait ApiMut: Mut<Scope> {
         // Return
         (name, id_ref, prev_id)
     }
+
+    /// Keep only the symbols for which `keep` returns true, letting it
+    /// rewrite them on the way. Symbol ids are re-assigned in order.
+    fn retain(&mut self, mut keep: impl FnMut(&str, &mut SymInfo) -> bool) {
+        let (sym_infos, sym_indx, ..) = parts_mut(self);
+
+        let old = std::mem::take(sym_infos);
+        sym_indx.clear();
+
+        for (name, SymID { mut sym_info, .. }) in old {
+            if keep(&name, &mut sym_info) {
+                let sym_id = sym_infos.len();
+                sym_indx.insert(name.clone(), sym_id);
+                sym_infos.push((name, SymID { sym_id, sym_info }));
+            }
+        }
+    }
 }
 pub(crate) trait ApiRef: Ref<Scope> {
     /// Lookup a symbol textual name by SymID
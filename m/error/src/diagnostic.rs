@@ -0,0 +1,158 @@
+//! Errors and warnings about a user's source, pointing into it.
+//!
+//! A [Diagnostic] carries copies of the source lines it points to, so it
+//! can be rendered on its own. `{}` renders it as plain text, `{:#}` with
+//! colors.
+use std::fmt;
+
+const RED: &str = "\x1b[38;5;160m";
+const YELLOW: &str = "\x1b[38;5;178m";
+const BLUE: &str = "\x1b[38;5;39m";
+const GREY: &str = "\x1b[38;5;246m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[m";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A short kebab-case name for the kind of problem, like
+    /// `unknown-symbol`.
+    pub code: &'static str,
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    /// Where the file of the primary label was included from, innermost
+    /// first.
+    pub included_from: Vec<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub position: Position,
+    /// The length in bytes of the labeled text, within its line.
+    pub len: usize,
+    /// The whole line the labeled text is on.
+    pub source_line: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code,
+            message: message.into(),
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            included_from: vec![],
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, label: Option<Label>) -> Self {
+        self.secondary.extend(label);
+        self
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let color = f.alternate();
+        let paint = |code: &'static str| if color { code } else { "" };
+        let (sev, reset, bold, grey) = (
+            paint(match self.severity {
+                Severity::Error => RED,
+                Severity::Warning => YELLOW,
+            }),
+            paint(RESET),
+            paint(BOLD),
+            paint(GREY),
+        );
+
+        writeln!(
+            f,
+            "{sev}{bold}{}[{}]{reset}{bold}: {}{reset}",
+            self.severity, self.code, self.message
+        )?;
+
+        let labels = self.primary.iter().map(|label| (label, sev, '^'));
+        let labels = labels.chain(self.secondary.iter().map(|label| (label, paint(BLUE), '-')));
+        let gutter = labels
+            .clone()
+            .map(|(label, _, _)| label.position.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(gutter);
+
+        for (idx, (label, mark_color, mark)) in labels.enumerate() {
+            let Position { line, column, .. } = &label.position;
+            let arrow = if idx == 0 { "-->" } else { ":::" };
+            writeln!(f, "{pad}{grey}{arrow}{reset} {}", label.position)?;
+            writeln!(f, "{pad} {grey}|{reset}")?;
+            writeln!(
+                f,
+                "{grey}{line:>gutter$} |{reset} {}",
+                label.source_line.trim_end()
+            )?;
+            let indent: String = label.source_line[..(column - 1).min(label.source_line.len())]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let marks = mark.to_string().repeat(label.len.max(1));
+            writeln!(
+                f,
+                "{pad} {grey}|{reset} {indent}{mark_color}{marks} {}{reset}",
+                label.message
+            )?;
+        }
+        for note in &self.notes {
+            writeln!(f, "{pad} {grey}={reset} {bold}note{reset}: {note}")?;
+        }
+        for position in &self.included_from {
+            writeln!(f, "{pad} {grey}={reset} included from {position}")?;
+        }
+        Ok(())
+    }
+}
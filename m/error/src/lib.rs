@@ -15,8 +15,11 @@ pub struct Error<Kind> {
     pub trace: Trace,
 }
 
+pub mod diagnostic;
 mod error_debug;
 
+pub use diagnostic::Diagnostic;
+
 pub type Result<T, K> = std::result::Result<T, Error<K>>;
 
 pub trait IntoResult<K, T> {
@@ -0,0 +1,352 @@
+//! Control of the running children, as a shell does.
+//!
+//! Each child runs in a process group of its own, along with whatever it
+//! spawns in turn, so that a signal to it reaches them all. SIGINT and
+//! SIGTERM, once caught, are forwarded to every running group, for the jobs
+//! to fail as they will; with none running, they end the program right away.
+//! Ending the program, by [exit], kills and reaps every group still running,
+//! and dies of the signal caught, if any.
+//!
+//! When the program runs in the foreground of a terminal, the terminal is
+//! handed to the group of the child last spawned while it runs, and then
+//! back, for it to read `/dev/tty` (as ssh and sudo do for passwords) and
+//! be stopped from it. A child stopped from the terminal stops the program
+//! as well, for the shell to resume both, as one job. A child reading or
+//! writing the terminal without having it is stopped by the system: it is
+//! killed, and fails.
+use {
+    super::{te, temg, Result},
+    std::{
+        fs::OpenOptions,
+        io::{self, Read, Write},
+        mem,
+        os::{
+            fd::{AsRawFd, IntoRawFd, RawFd},
+            unix::process::CommandExt,
+        },
+        process::{self, Child, Command, ExitStatus, Output},
+        ptr,
+        sync::{
+            atomic::{AtomicI32, Ordering},
+            Mutex, MutexGuard, OnceLock, PoisonError,
+        },
+        thread::spawn as spawn_thread,
+    },
+};
+
+/// The process groups of the running children, led by the children.
+static RUNNING: Mutex<Running> = Mutex::new(Running {
+    groups: Vec::new(),
+    ending: false,
+});
+
+/// The write end of the pipe the signal handler tells caught signals on.
+static CAUGHT_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// The last signal caught, or 0.
+static CAUGHT: AtomicI32 = AtomicI32::new(0);
+
+/// The terminal the program runs in the foreground of, if any.
+static TERMINAL: OnceLock<Option<RawFd>> = OnceLock::new();
+
+struct Running {
+    /// In the order they were spawned.
+    groups: Vec<libc::pid_t>,
+    /// Whether the program is ending, and spawns no more.
+    ending: bool,
+}
+
+/// Forward SIGINT and SIGTERM to the running children from now on, unless
+/// they are ignored, as they are for background jobs of a shell.
+pub fn catch_signals() -> Result<()> {
+    let (mut caught, tell) = te!(io::pipe());
+    CAUGHT_PIPE.store(tell.into_raw_fd(), Ordering::Relaxed);
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: plain system calls, with a handler only writing to a pipe
+        unsafe {
+            let mut current: libc::sigaction = std::mem::zeroed();
+            te!(check(libc::sigaction(signal, ptr::null(), &mut current)));
+            if current.sa_sigaction == libc::SIG_IGN {
+                continue;
+            }
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            te!(check(libc::sigaction(signal, &action, ptr::null_mut())));
+        }
+    }
+
+    spawn_thread(move || {
+        let mut signal = [0];
+        while let Ok(()) = caught.read_exact(&mut signal) {
+            let signal = libc::c_int::from(signal[0]);
+            CAUGHT.store(signal, Ordering::Relaxed);
+            let running = running();
+            if running.groups.is_empty() {
+                drop(running);
+                exit(1);
+            }
+            for &group in &running.groups {
+                kill_group(group, signal);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// End the program with the exit status `code`, after killing and reaping
+/// all running children, or else by the signal caught.
+pub fn exit(code: i32) -> ! {
+    {
+        let mut running = running();
+        running.ending = true;
+        for &group in &running.groups {
+            kill_group(group, libc::SIGKILL);
+        }
+        for group in running.groups.drain(..) {
+            // SAFETY: a plain system call, on a child of ours
+            unsafe { libc::waitpid(group, ptr::null_mut(), 0) };
+        }
+        if let Some(terminal) = terminal() {
+            // SAFETY: a plain system call
+            give_terminal(terminal, unsafe { libc::getpgrp() });
+        }
+    }
+
+    let _ = io::stdout().flush();
+    match CAUGHT.load(Ordering::Relaxed) {
+        0 => process::exit(code),
+        signal => {
+            // SAFETY: plain system calls, dying as asked to
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+            process::exit(128 + signal)
+        }
+    }
+}
+
+/// Spawn `cmd` in a process group of its own, given the terminal if the
+/// program has it.
+pub(crate) fn spawn(cmd: &mut Command) -> Result<Child> {
+    let mut running = running();
+    if running.ending {
+        temg!("Not spawning {:?}: ending", cmd)
+    }
+    cmd.process_group(0);
+    let terminal = terminal();
+    if let Some(terminal) = terminal {
+        // Also in the child, for it not to use the terminal before the
+        // program gives it
+        //
+        // SAFETY: only async-signal-safe calls
+        unsafe {
+            cmd.pre_exec(move || {
+                give_terminal(terminal, libc::getpid());
+                Ok(())
+            })
+        };
+    }
+    let child = te!(cmd.spawn());
+    let group = child.id() as libc::pid_t;
+    running.groups.push(group);
+    if let Some(terminal) = terminal {
+        give_terminal(terminal, group);
+    }
+    Ok(child)
+}
+
+/// Send `signal` to the group of `child`, if it still runs.
+pub(crate) fn signal(child: u32, signal: libc::c_int) {
+    let running = running();
+    if running.groups.contains(&(child as libc::pid_t)) {
+        kill_group(child as libc::pid_t, signal);
+    }
+}
+
+/// Wait for `child`, running no more once reaped.
+pub(crate) fn wait(child: &mut Child) -> io::Result<ExitStatus> {
+    let stopped = wait_running(child);
+    let status = child.wait();
+    reaped(child.id());
+    stopped?;
+    status
+}
+
+/// Wait for `child`, reading all its output.
+pub(crate) fn wait_with_output(mut child: Child) -> io::Result<Output> {
+    let stdout = child.stdout.take().map(|mut stdout| {
+        spawn_thread(move || {
+            let mut bytes = vec![];
+            stdout.read_to_end(&mut bytes).map(|_| bytes)
+        })
+    });
+    let status = wait(&mut child)?;
+    let stdout = match stdout {
+        Some(thread) => thread
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("Reading the output panicked")))?,
+        None => vec![],
+    };
+    Ok(Output {
+        status,
+        stdout,
+        stderr: vec![],
+    })
+}
+
+/// Whether `child` has exited, without waiting for it nor reaping it.
+pub(crate) fn exited(child: &Child) -> bool {
+    // SAFETY: a plain system call, on a child of ours
+    let (result, info) = unsafe {
+        let mut info: libc::siginfo_t = mem::zeroed();
+        let options = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+        let result = libc::waitid(libc::P_PID, child.id(), &mut info, options);
+        (result, info)
+    };
+    match check(result) {
+        // SAFETY: the pid is set only when the child has exited
+        Ok(()) => unsafe { info.si_pid() != 0 },
+        // Reaped already
+        Err(err) => err.raw_os_error() == Some(libc::ECHILD),
+    }
+}
+
+/// Wait for `child` to exit, without reaping it. A child stopped from the
+/// terminal stops the program as well, and one stopped for using the
+/// terminal without having it is killed.
+fn wait_running(child: &Child) -> io::Result<()> {
+    let group = child.id() as libc::pid_t;
+    loop {
+        let signal = match wait_id(group, libc::WEXITED | libc::WSTOPPED | libc::WNOWAIT)? {
+            Some(signal) => signal,
+            None => return Ok(()),
+        };
+        // Take the stop, told once
+        wait_id(group, libc::WSTOPPED)?;
+        match (signal, terminal()) {
+            (libc::SIGTTIN | libc::SIGTTOU, _) => {
+                kill_group(group, libc::SIGKILL);
+                let name = if signal == libc::SIGTTIN {
+                    "SIGTTIN"
+                } else {
+                    "SIGTTOU"
+                };
+                return Err(io::Error::other(format!(
+                    "Stopped by {name}, using the terminal without having it, and killed"
+                )));
+            }
+            (libc::SIGTSTP | libc::SIGSTOP, Some(terminal)) => suspend(terminal, group),
+            // Left for whoever stopped it to resume
+            _ => (),
+        }
+    }
+}
+
+/// Wait for a change of `child` among `options`, and tell the signal that
+/// stopped it, if so.
+fn wait_id(child: libc::pid_t, options: libc::c_int) -> io::Result<Option<libc::c_int>> {
+    loop {
+        // SAFETY: a plain system call, on a child of ours
+        let (result, info) = unsafe {
+            let mut info: libc::siginfo_t = mem::zeroed();
+            let result = libc::waitid(libc::P_PID, child as libc::id_t, &mut info, options);
+            (result, info)
+        };
+        match check(result) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            // Reaped already, with its status kept
+            Err(err) if err.raw_os_error() == Some(libc::ECHILD) => return Ok(None),
+            Err(err) => return Err(err),
+            // SAFETY: the status of a stopped child is its signal
+            Ok(()) if info.si_code == libc::CLD_STOPPED => {
+                return Ok(Some(unsafe { info.si_status() }))
+            }
+            Ok(()) => return Ok(None),
+        }
+    }
+}
+
+/// Stop the program along with `group`, stopped from `terminal`, and resume
+/// it when the program is.
+fn suspend(terminal: RawFd, group: libc::pid_t) {
+    // SAFETY: plain system calls, stopping as a shell job does
+    unsafe {
+        give_terminal(terminal, libc::getpgrp());
+        libc::kill(libc::getpid(), libc::SIGSTOP);
+    }
+    give_terminal(terminal, group);
+    kill_group(group, libc::SIGCONT);
+}
+
+fn reaped(child: u32) {
+    let mut running = running();
+    let child = child as libc::pid_t;
+    running.groups.retain(|&group| group != child);
+    let Some(terminal) = terminal() else {
+        return;
+    };
+    // SAFETY: plain system calls
+    unsafe {
+        if libc::tcgetpgrp(terminal) == child {
+            let group = running.groups.last().copied();
+            give_terminal(terminal, group.unwrap_or_else(|| libc::getpgrp()));
+        }
+    }
+}
+
+/// The terminal the program runs in the foreground of, if any.
+fn terminal() -> Option<RawFd> {
+    *TERMINAL.get_or_init(|| {
+        let tty = OpenOptions::new().read(true).open("/dev/tty").ok()?;
+        // SAFETY: plain system calls
+        let foreground = unsafe { libc::tcgetpgrp(tty.as_raw_fd()) == libc::getpgrp() };
+        foreground.then(|| tty.into_raw_fd())
+    })
+}
+
+/// Make `group` the foreground process group of `terminal`. The program
+/// is not stopped for it, from the background.
+fn give_terminal(terminal: RawFd, group: libc::pid_t) {
+    // SAFETY: async-signal-safe system calls, blocking SIGTTOU only around
+    // tcsetpgrp(3)
+    unsafe {
+        let mut ttou: libc::sigset_t = mem::zeroed();
+        let mut mask: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut ttou);
+        libc::sigaddset(&mut ttou, libc::SIGTTOU);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &ttou, &mut mask);
+        libc::tcsetpgrp(terminal, group);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &mask, ptr::null_mut());
+    }
+}
+
+fn running() -> MutexGuard<'static, Running> {
+    RUNNING.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn kill_group(group: libc::pid_t, signal: libc::c_int) {
+    // SAFETY: a plain system call, on a group led by a child not reaped yet
+    unsafe { libc::kill(-group, signal) };
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    let byte = signal as u8;
+    // SAFETY: write(2) is safe in a signal handler, on a pipe left open
+    unsafe {
+        libc::write(
+            CAUGHT_PIPE.load(Ordering::Relaxed),
+            &byte as *const u8 as *const libc::c_void,
+            1,
+        )
+    };
+}
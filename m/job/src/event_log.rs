@@ -0,0 +1,144 @@
+//! Logs of the events of jobs, as lines of JSON, for other programs to read.
+//!
+//! Each event is an object with its kind as `event`, and the time it was
+//! logged at, in seconds since the epoch, as `at`:
+//!
+//! ```text
+//! {"event":"call","at":1760868000.124000,"def":"main","origin":"<top level> at ./build.ds:9:1"}
+//! {"event":"spawn","at":1760868000.125000,"run":2,"pid":4242,"command":"sed","args":["s/a/b/"],"cwd":null,"env":{"LC_ALL":"C"},"origin":"main at ./build.ds:3:5"}
+//! {"event":"pipe","at":1760868000.125000,"from":1,"to":2}
+//! {"event":"output","at":1760868000.126000,"run":1,"bytes":14}
+//! {"event":"exit","at":1760868000.127000,"run":2,"code":0,"signal":null,"duration":0.002113}
+//! {"event":"collect","at":1760868000.127000,"run":2,"bytes":12}
+//! {"event":"return","at":1760868000.128000,"def":"main"}
+//! ```
+//!
+//! - `spawn`: the child `pid` of `run` was spawned, for `command` as invoked.
+//!   `origin` is where the job was made, or null, and `env` maps names to
+//!   values, or to null for the names removed.
+//! - `pipe`: the output of the run `from`, or of a text when null, is fed to
+//!   the run `to`.
+//! - `exit`: the child of `run` exited with `code`, or was killed by `signal`,
+//!   after running for `duration` seconds.
+//! - `collect`: the output of `run`, or of a text when null, was collected as
+//!   `bytes` bytes.
+//! - `output`: `run` output `bytes` bytes, fed to another job, to the output
+//!   of the script, or to the readers of a hub.
+//! - `call`: the def `def` was called at `origin`, or null if not known.
+//! - `return`: the def `def` returned.
+//!
+//! Fields are only ever added to these, and new kinds of events told with
+//! new names.
+use {
+    super::{
+        events::{self, Event},
+        te, Json, Result,
+    },
+    std::{
+        fmt::Write as _,
+        fs,
+        io::Write as _,
+        os::unix::process::ExitStatusExt,
+        path::Path,
+        sync::{Mutex, Once, PoisonError},
+        time::SystemTime,
+    },
+};
+
+static LOG: Mutex<Option<fs::File>> = Mutex::new(None);
+
+static LISTEN: Once = Once::new();
+
+/// Log all events from now on to the file at `path`, made anew, rather than
+/// where they were logged to before.
+pub fn log_events_to(path: &Path) -> Result<()> {
+    let file = te!(fs::File::create(path), "Creating event log {path:?}");
+    *LOG.lock().unwrap_or_else(PoisonError::into_inner) = Some(file);
+    LISTEN.call_once(|| events::listen(log));
+    Ok(())
+}
+
+fn log(event: &Event<'_>) {
+    let at = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let mut line = String::new();
+    let _ = match event {
+        Event::Spawn {
+            run,
+            pid,
+            cmd,
+            command,
+            origin,
+        } => {
+            let command = command.to_string_lossy();
+            let args = cmd
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            let cwd = cmd.get_current_dir().map(|dir| dir.to_string_lossy());
+            let _ = write!(
+                line,
+                "{{\"event\":\"spawn\",\"at\":{at:.6},\"run\":{run},\"pid\":{pid}"
+            );
+            let _ = write!(line, ",\"command\":{}", Json(&*command));
+            let _ = write!(line, ",\"args\":{}", Json(&args[..]));
+            let _ = write!(line, ",\"cwd\":{}", Json(cwd.as_deref()));
+            line.push_str(",\"env\":{");
+            for (i, (name, value)) in cmd.get_envs().enumerate() {
+                let sep = if i == 0 { "" } else { "," };
+                let value = value.map(|value| value.to_string_lossy());
+                let _ = write!(
+                    line,
+                    "{sep}{}:{}",
+                    Json(&*name.to_string_lossy()),
+                    Json(value.as_deref())
+                );
+            }
+            writeln!(line, "}},\"origin\":{}}}", Json(*origin))
+        }
+        Event::Pipe { from, to } => writeln!(
+            line,
+            "{{\"event\":\"pipe\",\"at\":{at:.6},\"from\":{},\"to\":{to}}}",
+            Json(*from)
+        ),
+        Event::Exit {
+            run,
+            status,
+            duration,
+            ..
+        } => writeln!(
+            line,
+            "{{\"event\":\"exit\",\"at\":{at:.6},\"run\":{run},\"code\":{},\"signal\":{},\"duration\":{:.6}}}",
+            Json(status.code()),
+            Json(status.signal()),
+            duration.as_secs_f64()
+        ),
+        Event::Collect { run, bytes } => writeln!(
+            line,
+            "{{\"event\":\"collect\",\"at\":{at:.6},\"run\":{},\"bytes\":{bytes}}}",
+            Json(*run)
+        ),
+        Event::Output { run, bytes } => writeln!(
+            line,
+            "{{\"event\":\"output\",\"at\":{at:.6},\"run\":{run},\"bytes\":{bytes}}}"
+        ),
+        Event::Call { def, origin } => writeln!(
+            line,
+            "{{\"event\":\"call\",\"at\":{at:.6},\"def\":{},\"origin\":{}}}",
+            Json(*def),
+            Json(*origin)
+        ),
+        Event::Return { def } => writeln!(
+            line,
+            "{{\"event\":\"return\",\"at\":{at:.6},\"def\":{}}}",
+            Json(*def)
+        ),
+    };
+
+    let mut log = LOG.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(file) = log.as_mut() {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
@@ -0,0 +1,153 @@
+//! Events of the lives of jobs, told to whoever listens (see [listen]).
+//!
+//! Each child spawned is a [Run], numbered in spawn order, and told as a
+//! [Event::Spawn] when started and an [Event::Exit] when reaped. In between,
+//! its inputs are told as [Event::Pipe]s, and its output, if collected, as an
+//! [Event::Collect], or else as an [Event::Output].
+//!
+//! Calls of defs are told as [Event::Call]s and their returns as
+//! [Event::Return]s, by the VM.
+use {
+    super::{Result, Timeout, Watchdog},
+    std::{
+        ffi::OsStr,
+        process::{Child, Command, ExitStatus},
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            PoisonError, RwLock,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// Something that happened to a job.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// The child `pid` of `run` was spawned for `cmd`, invoked as `command`
+    /// and made at `origin`.
+    Spawn {
+        run: u64,
+        pid: u32,
+        cmd: &'a Command,
+        command: &'a OsStr,
+        origin: Option<&'a str>,
+    },
+    /// The output of `from`, or a text when `None`, is fed to `to`.
+    Pipe { from: Option<u64>, to: u64 },
+    /// The output of `run`, or of a text when `None`, was collected, as
+    /// `bytes` bytes.
+    Collect { run: Option<u64>, bytes: usize },
+    /// `run` output `bytes` bytes, fed to another job, to the output of the
+    /// script, or to the readers of a hub.
+    Output { run: u64, bytes: usize },
+    /// The child of `run` exited with `status`, after running for
+    /// `duration`.
+    Exit {
+        run: u64,
+        cmd: &'a Command,
+        status: ExitStatus,
+        duration: Duration,
+    },
+    /// The def `def` was called at `origin`.
+    Call {
+        def: &'a str,
+        origin: Option<&'a str>,
+    },
+    /// The def `def` returned.
+    Return { def: &'a str },
+}
+
+type Listener = Box<dyn Fn(&Event<'_>) + Send + Sync>;
+
+static LISTENERS: RwLock<Vec<Listener>> = RwLock::new(Vec::new());
+
+/// Whether there are listeners, for events to be skipped cheaply otherwise.
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// The last run numbered.
+static RUNS: AtomicU64 = AtomicU64::new(0);
+
+/// Tell all events from now on to `listener`, from the threads they happen
+/// on.
+pub fn listen(listener: impl Fn(&Event<'_>) + Send + Sync + 'static) {
+    let mut listeners = LISTENERS.write().unwrap_or_else(PoisonError::into_inner);
+    listeners.push(Box::new(listener));
+    LISTENING.store(true, Ordering::Relaxed);
+}
+
+/// Whether events are listened to, for their details to be gathered.
+pub fn listening() -> bool {
+    LISTENING.load(Ordering::Relaxed)
+}
+
+/// Tell `event` to all listeners.
+pub fn emit(event: Event<'_>) {
+    if !listening() {
+        return;
+    }
+    let listeners = LISTENERS.read().unwrap_or_else(PoisonError::into_inner);
+    for listener in listeners.iter() {
+        listener(&event);
+    }
+}
+
+/// A child running: told as events, and killed if it runs out of time.
+#[derive(Debug)]
+pub struct Run {
+    id: u64,
+    started: Instant,
+    watchdog: Option<Watchdog>,
+}
+
+impl Run {
+    /// The number of the run, from 1.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Tell the child of `cmd`, invoked as `command`, was spawned, fed with
+    /// the outputs of the runs `inputs`.
+    pub(crate) fn start(
+        child: &Child,
+        cmd: &Command,
+        command: &OsStr,
+        origin: Option<&str>,
+        inputs: &[Option<u64>],
+        timeout: Option<Timeout>,
+    ) -> Self {
+        let id = RUNS.fetch_add(1, Ordering::Relaxed) + 1;
+        emit(Event::Spawn {
+            run: id,
+            pid: child.id(),
+            cmd,
+            command,
+            origin,
+        });
+        for &from in inputs {
+            emit(Event::Pipe { from, to: id });
+        }
+        Self {
+            id,
+            started: Instant::now(),
+            watchdog: timeout.map(|timeout| Watchdog::start(child, timeout)),
+        }
+    }
+
+    /// Tell the child of `cmd` exited with `status`, failing if it was
+    /// killed for running out of time.
+    pub(crate) fn finish(self, cmd: &Command, status: ExitStatus) -> Result<()> {
+        let Self {
+            id,
+            started,
+            watchdog,
+        } = self;
+        let timed = watchdog.map_or(Ok(()), |watchdog| watchdog.finish(cmd, status));
+        emit(Event::Exit {
+            run: id,
+            cmd,
+            status,
+            duration: started.elapsed(),
+        });
+        timed
+    }
+}
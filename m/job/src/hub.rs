@@ -0,0 +1,342 @@
+//! Jobs of which the output is read by many (see [Job::share]).
+//!
+//! The output of a hub is kept as it comes. Each reader is fed all of it,
+//! from the start: readers started early follow the output live, and late
+//! ones get it replayed. A failure of the job is a failure of each of its
+//! readers.
+//!
+//! Once all the readers of a hub are known, the output read by every one of
+//! them is dropped.
+use {
+    super::{
+        copy_command,
+        events::{self, Event},
+        te, temg, Cleanup, Error, ErrorKind, Job, Result, Run, System, SystemItem, Thread,
+        TimedOut,
+    },
+    std::{
+        fmt,
+        io::{self, Read, Write},
+        process::Command,
+        sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+        thread::spawn,
+    },
+};
+
+/// A job run once, for its output to be read by many.
+pub struct Hub {
+    cmd: Command,
+    output: SharedOutput,
+    /// Reads the output of the job, then waits for it.
+    pump: Option<Thread>,
+}
+
+/// A reader of the output of a hub, from the start.
+#[derive(Clone)]
+pub struct Reader(Arc<Cursor>);
+
+struct Cursor {
+    output: SharedOutput,
+    id: usize,
+}
+
+/// The output of a hub, growing until its job is done.
+#[derive(Clone)]
+struct SharedOutput(Arc<Shared>);
+
+struct Shared {
+    cmd: Command,
+    /// The run of the job, if it is a child.
+    run: Option<u64>,
+    /// How many readers the job has.
+    readers: usize,
+    state: Mutex<State>,
+    grown: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// The output not yet read by every reader.
+    bytes: Vec<u8>,
+    /// Where `bytes` start in the output.
+    start: usize,
+    /// How far in the output each reader is, by id, while it reads.
+    cursors: Vec<Option<usize>>,
+    /// No more readers come.
+    closed: bool,
+    done: bool,
+    failure: Option<Failure>,
+}
+
+/// A failure of the job, given to each reader. A timeout stays one.
+enum Failure {
+    Timeout(TimedOut),
+    Other(String),
+}
+
+impl Hub {
+    /// Start reading the output of `sys`, a running job, for its `readers`.
+    pub fn new(mut sys: System, readers: usize) -> Result<Self> {
+        let cmd = copy_command(&sys.cmd);
+        let SystemItem::Child(child) = &mut sys.item else {
+            temg!("Internal error: not a running job: {:?}", sys)
+        };
+        let mut stdout = te!(child.stdout.take(), "Missing output of {:?}", cmd);
+
+        let output = SharedOutput(Arc::new(Shared {
+            cmd: copy_command(&cmd),
+            run: sys.run.as_ref().map(Run::id),
+            readers,
+            state: <_>::default(),
+            grown: <_>::default(),
+        }));
+        let pump = {
+            let output = output.clone();
+            spawn(move || -> Result<()> {
+                let result = (|| -> Result<()> {
+                    te!(output.fill(&mut stdout));
+                    if let Some(run) = output.0.run {
+                        let bytes = output.state().end();
+                        events::emit(Event::Output { run, bytes });
+                    }
+                    te!(sys.cleanup());
+                    Ok(())
+                })();
+                output.finish(result.as_ref().err().map(Failure::of));
+                result
+            })
+        };
+
+        Ok(Self {
+            cmd,
+            output,
+            pump: Some(pump),
+        })
+    }
+
+    pub fn command(&self) -> &Command {
+        &self.cmd
+    }
+
+    /// A job feeding all the output to the job it is an input of.
+    pub fn reader(&self) -> Result<Job> {
+        Ok(Job::System(te!(self.output.reader(&self.cmd))))
+    }
+
+    /// A reader of the output, that waits for the job of the hub when
+    /// cleaned up. It is the last one.
+    pub fn into_system(mut self) -> Result<System> {
+        let mut sys = te!(self.output.reader(&self.cmd));
+        sys.cleanup.extend(self.pump.take().map(Cleanup::Thread));
+        Ok(sys)
+    }
+
+    /// Wait for the job of the hub.
+    pub fn cleanup(mut self) -> Result<()> {
+        if let Some(pump) = self.pump.take() {
+            te!(Cleanup::Thread(pump).perform());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Hub {
+    /// A hub gone gets no more readers.
+    fn drop(&mut self) {
+        self.output.state().close();
+    }
+}
+
+impl Reader {
+    /// Another reader of the same output, from the start, as of `cmd`.
+    pub fn reader(&self, cmd: &Command) -> Result<System> {
+        Ok(te!(self.0.output.reader(cmd)))
+    }
+
+    /// The run of the job, if it is a child.
+    pub fn run(&self) -> Option<u64> {
+        self.0.output.0.run
+    }
+
+    /// Write all the output to `out`, as it comes. A reader that stops
+    /// reading early has had all it wanted.
+    pub fn feed(&self, out: &mut impl Write) -> Result<()> {
+        let Cursor { output, id } = &*self.0;
+        loop {
+            let (chunk, pos) = {
+                let mut state = output.state();
+                let pos = te!(state.cursor(*id));
+                while state.end() == pos && !state.done {
+                    state = output.grow(state);
+                }
+                if state.end() == pos {
+                    return state.result();
+                }
+                (state.bytes[pos - state.start..].to_vec(), pos)
+            };
+            match out.write_all(&chunk) {
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                written => te!(written),
+            }
+            output.state().advance(*id, pos + chunk.len());
+        }
+    }
+
+    /// All the output, once the job is done.
+    pub fn wait(&self) -> Result<Vec<u8>> {
+        let Cursor { output, id } = &*self.0;
+        let mut state = output.state();
+        while !state.done {
+            state = output.grow(state);
+        }
+        te!(state.result());
+        let pos = te!(state.cursor(*id));
+        Ok(state.bytes[pos - state.start..].to_vec())
+    }
+}
+
+impl Drop for Cursor {
+    fn drop(&mut self) {
+        let mut state = self.output.state();
+        if let Some(cursor) = state.cursors.get_mut(self.id) {
+            *cursor = None;
+        }
+        state.trim();
+    }
+}
+
+impl SharedOutput {
+    /// A job feeding all the output, as of `cmd`.
+    fn reader(&self, cmd: &Command) -> Result<System> {
+        let id = {
+            let mut state = self.state();
+            if state.closed {
+                temg!(
+                    "The output of {:?} is not kept for more than its {} readers",
+                    self.0.cmd,
+                    self.0.readers
+                )
+            }
+            state.cursors.push(Some(0));
+            if state.cursors.len() >= self.0.readers {
+                state.close();
+            }
+            state.cursors.len() - 1
+        };
+        let reader = Reader(Arc::new(Cursor {
+            output: self.clone(),
+            id,
+        }));
+        Ok(System {
+            cmd: copy_command(cmd),
+            item: SystemItem::Shared(reader),
+            cleanup: <_>::default(),
+            init: <_>::default(),
+            tee: None,
+            run: None,
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn grow<'s>(&self, state: MutexGuard<'s, State>) -> MutexGuard<'s, State> {
+        self.0
+            .grown
+            .wait(state)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn fill(&self, source: &mut impl Read) -> io::Result<()> {
+        let mut chunk = [0; 8192];
+        loop {
+            let n = match source.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            let mut state = self.state();
+            state.bytes.extend_from_slice(&chunk[..n]);
+            state.trim();
+            self.0.grown.notify_all();
+        }
+    }
+
+    fn finish(&self, failure: Option<Failure>) {
+        let mut state = self.state();
+        state.done = true;
+        state.failure = failure;
+        self.0.grown.notify_all();
+    }
+}
+
+impl State {
+    /// Where the output kept ends.
+    fn end(&self) -> usize {
+        self.start + self.bytes.len()
+    }
+
+    fn cursor(&self, id: usize) -> Result<usize> {
+        Ok(te!(
+            self.cursors.get(id).copied().flatten(),
+            "Internal error: no reader {}",
+            id
+        ))
+    }
+
+    fn advance(&mut self, id: usize, pos: usize) {
+        self.cursors[id] = Some(pos);
+        self.trim();
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.trim();
+    }
+
+    /// Drop the output read by every reader, once no more readers come.
+    fn trim(&mut self) {
+        if !self.closed {
+            return;
+        }
+        let read = self.cursors.iter().flatten().min().copied();
+        let read = read.unwrap_or_else(|| self.end());
+        self.bytes.drain(..read - self.start);
+        self.start = read;
+    }
+
+    fn result(&self) -> Result<()> {
+        match &self.failure {
+            Some(Failure::Timeout(timed_out)) => te!(Err::<(), _>(timed_out.clone())),
+            Some(Failure::Other(failure)) => temg!("{failure}"),
+            None => (),
+        }
+        Ok(())
+    }
+}
+
+impl Failure {
+    fn of(err: &Error) -> Self {
+        match &err.kind {
+            ErrorKind::Timeout(timed_out) => Self::Timeout(timed_out.clone()),
+            ErrorKind::Msg(msg) => Self::Other(msg.to_owned()),
+            other => Self::Other(format!("{other:?}")),
+        }
+    }
+}
+
+impl fmt::Debug for Hub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Hub").field(&self.cmd).finish()
+    }
+}
+
+impl fmt::Debug for Reader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.0.output.state();
+        let done = if state.done { "done" } else { "running" };
+        write!(f, "Reader({} bytes kept, {done})", state.bytes.len())
+    }
+}
@@ -3,17 +3,39 @@ pub const VERSION: &str = "0.0.1";
 use {
     error::{ldebug, te, temg},
     std::{
-        fmt, io, mem,
+        borrow::Cow,
+        ffi::OsStr,
+        fmt,
+        io::{self, BufRead, Read, Write},
+        mem,
+        path::Path,
         process::{Child, Command, ExitStatus, Stdio},
+        str::FromStr,
         thread::{spawn, JoinHandle},
     },
 };
 
+mod control;
+pub use control::{catch_signals, exit};
+mod event_log;
+pub use event_log::log_events_to;
+pub mod events;
+use events::{Event, Run};
+mod hub;
+pub use hub::{Hub, Reader};
+mod json;
+pub use json::Json;
+mod timeout;
+pub use timeout::{TimedOut, Timeout, Watchdog};
+mod trace;
+pub use trace::Trace;
+
 error::Error! {
     Msg = String
     Io = io::Error
     Thread = Box<dyn std::any::Any + Send>
     Utf8 = std::string::FromUtf8Error
+    Timeout = TimedOut
 }
 
 either::either![
@@ -22,18 +44,27 @@ either::either![
         Null,
         Spec,
         System,
-        Buffer
+        Buffer,
+        Hub
 ];
 
 #[derive(Debug)]
 pub struct Spec {
     pub cmd: Command,
     pub input: Vec<Job>,
+    pub tee: Option<Tee>,
+    pub timeout: Option<Timeout>,
+    /// Where the job was made, as told in events (see [events::listening]).
+    pub origin: Option<String>,
+    /// The name the program was invoked by, as told in events, if not the
+    /// program itself.
+    pub name: Option<String>,
 }
 
 pub enum SystemItem {
     Child(Child),
     Buffer(Vec<u8>),
+    Shared(Reader),
 }
 
 #[derive(Debug)]
@@ -42,13 +73,33 @@ pub struct System {
     pub item: SystemItem,
     pub cleanup: Vec<Cleanup>,
     pub init: Vec<Init>,
+    pub tee: Option<Tee>,
+    pub run: Option<Run>,
+}
+
+/// Where the output of a job is streamed to, line by line as it comes, when
+/// it is captured.
+///
+/// Parsed from `stdout` or `stderr`, optionally followed by `:` and the
+/// prefix of each line, which is otherwise the file name of the program and
+/// `: `.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tee {
+    pub to: TeeTo,
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeeTo {
+    Stdout,
+    Stderr,
 }
 
 pub struct Init(Box<dyn FnOnce(&mut Child) -> Result<Cleanup> + Send>);
 
 #[derive(Debug)]
 pub enum Cleanup {
-    Child(Child, Command),
+    Child(Child, Command, Option<Box<Run>>),
     Thread(Thread),
 }
 
@@ -65,6 +116,10 @@ impl From<Command> for Job {
         Self::Spec(Spec {
             cmd,
             input: <_>::default(),
+            tee: None,
+            timeout: None,
+            origin: None,
+            name: None,
         })
     }
 }
@@ -99,27 +154,46 @@ impl Job {
         })
     }
 
+    /// Add `input_job` as an input of this job.
+    ///
+    /// A job yet to run, or running, is read directly by this job, unless
+    /// it is shared (see [Job::share]). Other jobs are left for further uses.
     pub fn add_input_job(&mut self, input_job: &mut Job) -> Result<()> {
-        self.as_spec_mut().map(|Spec { input, .. }| {
-            let input_job = match input_job {
-                Job::Null(_) => Job::Null(()),
-                Job::Buffer(Buffer::Null) => Job::Buffer(Buffer::Null),
-
-                Job::Buffer(Buffer::Bytes(_, buf))
-                | Job::System(System {
-                    item: SystemItem::Buffer(buf),
-                    ..
-                }) => echo_buffer_job(buf),
-
-                Job::Buffer(Buffer::String(_, _))
-                | Job::System(System {
-                    item: SystemItem::Child(_),
-                    ..
-                })
-                | Job::Spec(_) => mem::take(input_job),
-            };
-            input.push(input_job)
-        })
+        let input_job = match input_job {
+            Job::Null(_) => Job::Null(()),
+            Job::Buffer(Buffer::Null) => Job::Buffer(Buffer::Null),
+
+            Job::Buffer(Buffer::Bytes(_, buf))
+            | Job::System(System {
+                item: SystemItem::Buffer(buf),
+                ..
+            }) => echo_buffer_job(buf),
+            // Also used as an argument, and kept for further uses
+            Job::Buffer(Buffer::String(_, s)) => echo_buffer_job(s),
+
+            Job::System(System {
+                cmd,
+                item: SystemItem::Shared(reader),
+                ..
+            }) => Job::System(te!(reader.reader(cmd))),
+            Job::Hub(hub) => te!(hub.reader()),
+            Job::System(System {
+                item: SystemItem::Child(_),
+                ..
+            })
+            | Job::Spec(_) => mem::take(input_job),
+        };
+        te!(self.as_spec_mut()).input.push(input_job);
+        Ok(())
+    }
+
+    /// The command run by this job, unless it is a buffer.
+    pub fn command(&self) -> Option<&Command> {
+        match self {
+            Self::Spec(Spec { cmd, .. }) | Self::System(System { cmd, .. }) => Some(cmd),
+            Self::Hub(hub) => Some(hub.command()),
+            Self::Buffer(_) | Self::Null(_) => None,
+        }
     }
 
     pub fn as_bytes(&self) -> Result<&[u8]> {
@@ -134,6 +208,7 @@ impl Job {
         Ok(match self {
             Self::Spec(spec) => te!(spawn_spec(spec, capture)),
             Self::System(s) => s,
+            Self::Hub(hub) => te!(hub.into_system()),
             Self::Buffer(buf) => echo_buffer(buf, capture),
             Self::Null(_) => temg!("Cannot pipe null Job"),
         })
@@ -146,6 +221,7 @@ impl Job {
         Ok(match self {
             Self::Spec(s) => te!(collect_output(te!(spawn_spec(s, true)))),
             Self::System(sys) => te!(collect_output(sys)),
+            Self::Hub(hub) => te!(collect_output(te!(hub.into_system()))),
             Self::Buffer(buf) => buf,
             other => panic!("{:?}", other),
         })
@@ -163,11 +239,16 @@ impl Job {
         Ok(te!(te!(self.as_buffer_mut()).make_string()))
     }
 
+    pub fn make_string_lossy(&mut self) -> Result<Cow<'_, str>> {
+        Ok(te!(te!(self.as_buffer_mut()).make_string_lossy()))
+    }
+
     pub fn cleanup(&mut self) -> Result<()> {
         ldebug!("cleanup {:?}", self);
-        let job = mem::take(self);
-        let sys = te!(job.into_pipe(false));
-        te!(sys.cleanup());
+        match mem::take(self) {
+            Self::Hub(hub) => te!(hub.cleanup()),
+            job => te!(te!(job.into_pipe(false)).cleanup()),
+        }
         Ok(())
     }
     pub fn collect(&mut self) -> Result<()> {
@@ -176,6 +257,20 @@ impl Job {
     pub fn pipe(&mut self) -> Result<()> {
         self.make_pipe(true)
     }
+    /// Pipe this job, for its output to be read by its `readers`, through a
+    /// [Hub] if it runs.
+    pub fn share(&mut self, readers: usize) -> Result<()> {
+        te!(self.make_pipe(true));
+        if let Self::System(System {
+            item: SystemItem::Child(_),
+            ..
+        }) = self
+        {
+            let sys = te!(mem::take(self).into_pipe(true));
+            *self = Self::Hub(te!(Hub::new(sys, readers)));
+        }
+        Ok(())
+    }
 }
 
 fn echo_buffer_job<B: Byteable + ToOwned<Owned = B>>(buf: &B) -> Job {
@@ -196,6 +291,8 @@ fn echo_buffer(buf: Buffer, capture: bool) -> System {
         item: SystemItem::Buffer(buf.take_bytes()),
         cleanup: <_>::default(),
         init: <_>::default(),
+        tee: None,
+        run: None,
     };
 
     ldebug!(
@@ -208,20 +305,50 @@ fn echo_buffer(buf: Buffer, capture: bool) -> System {
 
 fn collect_output(sys: System) -> Result<Buffer> {
     let System {
-        cmd, item, cleanup, ..
+        cmd,
+        item,
+        cleanup,
+        tee,
+        run,
+        ..
     } = sys;
 
-    let stdout = match item {
-        SystemItem::Child(child) => {
-            let output = te!(child.wait_with_output());
+    let (run, stdout) = match item {
+        SystemItem::Child(mut child) => {
+            let id = run.as_ref().map(Run::id);
+            let (status, stdout) = match tee {
+                Some(tee) => {
+                    let stdout = te!(tee_output(&cmd, &mut child, &tee));
+                    (
+                        te!(control::wait(&mut child), "Waiting for {cmd:?}"),
+                        stdout,
+                    )
+                }
+                None => {
+                    let output = te!(control::wait_with_output(child), "Waiting for {cmd:?}");
+                    (output.status, output.stdout)
+                }
+            };
 
-            te!(check_exit_status(&cmd, output.status));
-            te!(Cleanup::all(cleanup));
+            let timed = run.map_or(Ok(()), |run| run.finish(&cmd, status));
+            let cleaned = Cleanup::all(cleanup);
+            te!(timed);
+            te!(check_exit_status(&cmd, status));
+            te!(cleaned);
 
-            output.stdout
+            (id, stdout)
+        }
+        SystemItem::Buffer(buf) => (None, buf),
+        SystemItem::Shared(reader) => {
+            let stdout = te!(reader.wait());
+            te!(Cleanup::all(cleanup));
+            (reader.run(), stdout)
         }
-        SystemItem::Buffer(buf) => buf,
     };
+    events::emit(Event::Collect {
+        run,
+        bytes: stdout.len(),
+    });
 
     let buffer = Buffer::Bytes(cmd, stdout);
 
@@ -229,8 +356,78 @@ fn collect_output(sys: System) -> Result<Buffer> {
     Ok(buffer)
 }
 
-fn connect_input(cmd: &mut Command, input: Job) -> Result<System> {
+/// Read the output of `child`, streaming it to `tee` as it comes.
+fn tee_output(cmd: &Command, child: &mut Child, tee: &Tee) -> Result<Vec<u8>> {
+    let stdout = te!(child.stdout.take(), "Missing output of {:?}", cmd);
+    let mut stdout = io::BufReader::new(stdout);
+    let prefix = match &tee.prefix {
+        Some(prefix) => prefix.to_owned(),
+        None => {
+            let program = Path::new(cmd.get_program());
+            let name = program.file_name().unwrap_or(program.as_os_str());
+            format!("{}: ", name.to_string_lossy())
+        }
+    };
+
+    let mut output = vec![];
+    loop {
+        let start = output.len();
+        if te!(stdout.read_until(b'\n', &mut output)) == 0 {
+            break;
+        }
+        let line = &output[start..];
+        let written = match tee.to {
+            TeeTo::Stdout => write_line(io::stdout().lock(), &prefix, line),
+            TeeTo::Stderr => write_line(io::stderr().lock(), &prefix, line),
+        };
+        te!(written);
+    }
+    Ok(output)
+}
+
+/// Copy the output of `run` to `out`, telling how much it was. A reader
+/// that stops reading early has had all it wanted.
+fn stream_output(run: Option<u64>, mut output: impl Read, mut out: impl Write) -> Result<()> {
+    let mut chunk = [0; 8192];
+    let mut bytes = 0;
+    loop {
+        let n = match output.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => te!(Err(err)),
+        };
+        match out.write_all(&chunk[..n]).and_then(|()| out.flush()) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => break,
+            written => te!(written),
+        }
+        bytes += n;
+    }
+    if let Some(run) = run {
+        events::emit(Event::Output { run, bytes });
+    }
+    Ok(())
+}
+
+fn write_line(mut out: impl Write, prefix: &str, line: &[u8]) -> io::Result<()> {
+    out.write_all(prefix.as_bytes())?;
+    out.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+/// A copy of `cmd`, telling what it runs.
+fn copy_command(cmd: &Command) -> Command {
+    let mut copy = Command::new(cmd.get_program());
+    copy.args(cmd.get_args());
+    copy
+}
+
+fn connect_input(cmd: &mut Command, input: Job, sources: &mut Vec<Option<u64>>) -> Result<System> {
     let mut inp_sys = te!(input.into_pipe(true));
+    sources.push(inp_sys.source());
 
     match &mut inp_sys.item {
         SystemItem::Child(child) => {
@@ -240,7 +437,18 @@ fn connect_input(cmd: &mut Command, input: Job) -> Result<System> {
                 child,
                 cmd
             );
-            cmd.stdin(inp_stdout);
+            if events::listening() {
+                // Through here, to be told how much it is
+                cmd.stdin(Stdio::piped());
+                let run = inp_sys.run.as_ref().map(Run::id);
+                inp_sys.init.push(Init(Box::new(move |child| {
+                    let stdin = te!(child.stdin.take(), "Missing stdin on child {:?}", child);
+                    let thread = spawn(move || stream_output(run, inp_stdout, stdin));
+                    Ok(Cleanup::Thread(thread))
+                })));
+            } else {
+                cmd.stdin(inp_stdout);
+            }
         }
         SystemItem::Buffer(ref buf) => {
             cmd.stdin(Stdio::piped());
@@ -257,13 +465,25 @@ fn connect_input(cmd: &mut Command, input: Job) -> Result<System> {
                 })
             })));
         }
+        SystemItem::Shared(reader) => {
+            cmd.stdin(Stdio::piped());
+            let reader = reader.clone();
+            inp_sys.init.push(Init(Box::new(move |child| {
+                let mut stdin = te!(child.stdin.take(), "Missing stdin on child {:?}", child);
+                Ok(Cleanup::Thread(spawn(move || reader.feed(&mut stdin))))
+            })));
+        }
     }
 
     ldebug!("Connect input: {:?}", inp_sys);
     Ok(inp_sys)
 }
 
-fn connect_inputs(cmd: &mut Command, inputs: Vec<Job>) -> Result<System> {
+fn connect_inputs(
+    cmd: &mut Command,
+    inputs: Vec<Job>,
+    sources: &mut Vec<Option<u64>>,
+) -> Result<System> {
     cmd.stdin(Stdio::piped());
 
     let mut inp_sys = System {
@@ -271,12 +491,15 @@ fn connect_inputs(cmd: &mut Command, inputs: Vec<Job>) -> Result<System> {
         init: <_>::default(),
         cleanup: <_>::default(),
         item: SystemItem::Buffer(vec![]), // bogus item, unused
+        tee: None,
+        run: None,
     };
 
     let systems = te!(inputs
         .into_iter()
         .map(|job| job.into_pipe(true))
         .collect::<Result<Vec<_>>>());
+    sources.extend(systems.iter().map(System::source));
 
     // Systems that come from .into_pipe(), do not have any special inits.
     // We only need to care about propagating cleanups.
@@ -293,13 +516,17 @@ fn connect_inputs(cmd: &mut Command, inputs: Vec<Job>) -> Result<System> {
                 for mut inp_sys in systems {
                     match &mut inp_sys.item {
                         SystemItem::Child(inp_child) => {
-                            let mut stdout =
+                            let stdout =
                                 te!(inp_child.stdout.take(), "Missing output of {:?}", inp_child);
-                            te!(io::copy(&mut stdout, &mut stdin));
+                            let run = inp_sys.run.as_ref().map(Run::id);
+                            te!(stream_output(run, stdout, &mut stdin));
                         }
                         SystemItem::Buffer(bytes) => {
                             te!(io::copy(&mut bytes.as_slice(), &mut stdin));
                         }
+                        SystemItem::Shared(reader) => {
+                            te!(reader.feed(&mut stdin));
+                        }
                     }
 
                     let (inits, cleanups) = inp_sys.into_init_cleanup();
@@ -320,11 +547,22 @@ fn connect_inputs(cmd: &mut Command, inputs: Vec<Job>) -> Result<System> {
     Ok(inp_sys)
 }
 
-fn spawn_spec(Spec { mut cmd, mut input }: Spec, capture: bool) -> Result<System> {
+fn spawn_spec(
+    Spec {
+        mut cmd,
+        mut input,
+        tee,
+        timeout,
+        origin,
+        name,
+    }: Spec,
+    capture: bool,
+) -> Result<System> {
+    let mut sources = vec![];
     let inp_sys: Option<System> = match input.as_mut_slice() {
         [] => None,
-        [input] => Some(te!(connect_input(&mut cmd, mem::take(input)))),
-        _ => Some(te!(connect_inputs(&mut cmd, input))),
+        [input] => Some(te!(connect_input(&mut cmd, mem::take(input), &mut sources))),
+        _ => Some(te!(connect_inputs(&mut cmd, input, &mut sources))),
     };
 
     let mut cleanup: Vec<Cleanup> = <_>::default();
@@ -337,14 +575,17 @@ fn spawn_spec(Spec { mut cmd, mut input }: Spec, capture: bool) -> Result<System
         vec![]
     };
 
-    if capture {
+    // An output inherited is streamed through here when events are
+    // listened to, to be told how much it is
+    let streamed = !capture && events::listening();
+    if capture || streamed {
         cmd.stdout(Stdio::piped());
     } else {
         cmd.stdout(Stdio::inherit());
     }
 
     let mut child = te!(
-        cmd.spawn(),
+        control::spawn(&mut cmd),
         "Spawning {:?} in {:?}",
         cmd,
         cmd.get_current_dir()
@@ -353,12 +594,23 @@ fn spawn_spec(Spec { mut cmd, mut input }: Spec, capture: bool) -> Result<System
     for init in inp_inits {
         cleanup.push(te!(init.0(&mut child)));
     }
+    let command = name.as_deref().map_or(cmd.get_program(), OsStr::new);
+    let run = Run::start(&child, &cmd, command, origin.as_deref(), &sources, timeout);
+    if streamed {
+        let stdout = te!(child.stdout.take(), "Missing output of {:?}", cmd);
+        let id = run.id();
+        cleanup.push(Cleanup::Thread(spawn(move || {
+            stream_output(Some(id), stdout, io::stdout())
+        })));
+    }
 
     let sys = System {
         cmd,
         item: SystemItem::Child(child),
         cleanup,
         init: <_>::default(),
+        tee,
+        run: Some(run),
     };
 
     ldebug!(
@@ -380,9 +632,12 @@ impl Cleanup {
     fn perform(self) -> Result<()> {
         use Cleanup as C;
         Ok(match self {
-            C::Child(mut child, cmd) => {
+            C::Child(mut child, cmd, run) => {
                 ldebug!("Child wait {:?}", cmd);
-                let status = te!(child.wait());
+                let status = te!(control::wait(&mut child), "Waiting for {cmd:?}");
+                if let Some(run) = run {
+                    te!((*run).finish(&cmd, status));
+                }
                 te!(check_exit_status(&cmd, status));
             }
             C::Thread(handle) => {
@@ -398,11 +653,24 @@ impl Cleanup {
         C: IntoIterator,
         C::Item: Into<Cleanup>,
     {
-        cleanups
-            .into_iter()
-            .map(<_>::into)
-            .map(Cleanup::perform)
-            .collect()
+        // All are waited for, even after a failure, and a timeout is told
+        // over the failures of the feeders of the job it killed
+        let mut result = Ok(());
+        for cleanup in cleanups {
+            match (&result, cleanup.into().perform()) {
+                (_, Ok(())) => (),
+                (Ok(()), failed) => result = failed,
+                (
+                    Err(_),
+                    failed @ Err(Error {
+                        kind: ErrorKind::Timeout(_),
+                        ..
+                    }),
+                ) => result = failed,
+                (Err(_), Err(_)) => (),
+            }
+        }
+        result
     }
 }
 
@@ -436,17 +704,52 @@ impl Buffer {
                 *self = Buffer::String(cmd, string);
                 te!(self.as_str())
             }
-            Buffer::Bytes(cmd, bytes) => {
-                let s = te!(
-                    String::from_utf8(bytes),
-                    "Convert output of {:?} to String",
-                    cmd
-                );
-                *self = Buffer::String(cmd, s);
-                te!(self.make_string())
-            }
+            Buffer::Bytes(cmd, bytes) => match String::from_utf8(bytes) {
+                Ok(s) => {
+                    *self = Buffer::String(cmd, s);
+                    te!(self.make_string())
+                }
+                Err(err) => {
+                    let msg = format!(
+                        "Output of {:?} is not UTF-8 text (invalid byte at offset {})",
+                        cmd,
+                        err.utf8_error().valid_up_to()
+                    );
+                    // The output is still good as bytes
+                    *self = Buffer::Bytes(cmd, err.into_bytes());
+                    temg!("{msg}")
+                }
+            },
         })
     }
+
+    /// Like [Self::make_string], but replacing invalid UTF-8 with U+FFFD.
+    /// Output that is not UTF-8 is left as bytes, for uses as an input.
+    pub fn make_string_lossy(&mut self) -> Result<Cow<'_, str>> {
+        let invalid =
+            matches!(self, Buffer::Bytes(_, bytes) if std::str::from_utf8(bytes).is_err());
+        if invalid {
+            return Ok(String::from_utf8_lossy(self.as_bytes()));
+        }
+        Ok(Cow::Borrowed(te!(self.make_string())))
+    }
+}
+
+impl FromStr for Tee {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (to, prefix) = match s.split_once(':') {
+            Some((to, prefix)) => (to, Some(prefix.to_owned())),
+            None => (s, None),
+        };
+        let to = match to {
+            "stdout" => TeeTo::Stdout,
+            "stderr" => TeeTo::Stderr,
+            other => temg!("Cannot tee to {other:?}: not stdout or stderr"),
+        };
+        Ok(Self { to, prefix })
+    }
 }
 
 impl Default for Buffer {
@@ -462,10 +765,12 @@ impl System {
             item,
             mut cleanup,
             init,
+            run,
+            ..
         } = self;
         match item {
             SystemItem::Child(child) => {
-                cleanup.push(Cleanup::Child(child, cmd));
+                cleanup.push(Cleanup::Child(child, cmd, run.map(Box::new)));
             }
             _ => (),
         }
@@ -476,6 +781,28 @@ impl System {
         ldebug!("cleanup {:?}", self);
         Cleanup::all(self.into_init_cleanup().1)
     }
+
+    /// Whether the job and all its clean-up are done, for the clean-up not
+    /// to block.
+    pub fn finished(&self) -> bool {
+        let done = match &self.item {
+            SystemItem::Child(child) => control::exited(child),
+            SystemItem::Buffer(_) | SystemItem::Shared(_) => true,
+        };
+        done && self.cleanup.iter().all(|cleanup| match cleanup {
+            Cleanup::Child(child, ..) => control::exited(child),
+            Cleanup::Thread(handle) => handle.is_finished(),
+        })
+    }
+
+    /// The run the output of this job comes from, if any.
+    fn source(&self) -> Option<u64> {
+        match &self.item {
+            SystemItem::Child(_) => self.run.as_ref().map(Run::id),
+            SystemItem::Shared(reader) => reader.run(),
+            SystemItem::Buffer(_) => None,
+        }
+    }
 }
 
 impl fmt::Debug for Init {
@@ -533,6 +860,7 @@ impl fmt::Debug for SystemItem {
         match self {
             Self::Child(child) => f.debug_tuple("Child").field(child).finish(),
             Self::Buffer(bs) => fmt_sane!(bs, |bs| write!(f, "Buffer({bs})")),
+            Self::Shared(output) => f.debug_tuple("Shared").field(output).finish(),
         }
     }
 }
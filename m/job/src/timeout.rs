@@ -0,0 +1,113 @@
+//! Bounds on how long a job runs.
+//!
+//! A job spawned with a [Timeout] is watched by a thread of its own, which
+//! kills it when its time is up. Its clean-up then fails with [TimedOut],
+//! rather than with the failures of its input feeders, which lose their
+//! reader.
+use {
+    super::{control, te, temg, Error, Result},
+    std::{
+        fmt,
+        os::unix::process::ExitStatusExt,
+        process::{Child, Command, ExitStatus},
+        str::FromStr,
+        sync::mpsc,
+        thread::{spawn, JoinHandle},
+        time::Duration,
+    },
+};
+
+/// How long a job may run, parsed from a number of milliseconds (`ms`),
+/// seconds (`s`, or no unit), minutes (`m`) or hours (`h`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout(pub Duration);
+
+/// The failure of a job killed for running out of time.
+#[derive(Debug, Clone)]
+pub struct TimedOut {
+    pub command: String,
+    pub after: Duration,
+}
+
+/// Kills a child still running when its time is up, with its process group.
+pub struct Watchdog {
+    after: Duration,
+    cancel: mpsc::Sender<()>,
+    /// Whether it killed the child.
+    thread: JoinHandle<bool>,
+}
+
+impl Watchdog {
+    pub fn start(child: &Child, Timeout(after): Timeout) -> Self {
+        let child = child.id();
+        let (cancel, canceled) = mpsc::channel();
+        let thread = spawn(move || match canceled.recv_timeout(after) {
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                control::signal(child, libc::SIGKILL);
+                true
+            }
+            _ => false,
+        });
+        Self {
+            after,
+            cancel,
+            thread,
+        }
+    }
+
+    /// Stop watching the child of `cmd`, done running with `status`,
+    /// failing if it was killed. A child that exited on its own before the
+    /// kill did not time out.
+    pub fn finish(self, cmd: &Command, status: ExitStatus) -> Result<()> {
+        let Self {
+            after,
+            cancel,
+            thread,
+        } = self;
+        let _ = cancel.send(());
+        let killed = te!(thread.join());
+        if killed && status.signal() == Some(libc::SIGKILL) {
+            let command = format!("{cmd:?}");
+            te!(Err::<(), _>(TimedOut { command, after }));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Timeout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+        let Ok(number) = number.parse::<u64>() else {
+            temg!("Invalid timeout {s:?}: not a number of ms, s, m or h")
+        };
+        let secs = |per_unit: u64| -> Result<Duration> {
+            match number.checked_mul(per_unit) {
+                Some(secs) => Ok(Duration::from_secs(secs)),
+                None => temg!("Invalid timeout {s:?}: too long"),
+            }
+        };
+        let duration = match unit {
+            "ms" => Duration::from_millis(number),
+            "" | "s" => Duration::from_secs(number),
+            "m" => te!(secs(60)),
+            "h" => te!(secs(60 * 60)),
+            other => temg!("Invalid timeout {s:?}: unknown unit {other:?}"),
+        };
+        Ok(Self(duration))
+    }
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { command, after } = self;
+        write!(f, "{command} timed out after {after:?}")
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Watchdog").field(&self.after).finish()
+    }
+}
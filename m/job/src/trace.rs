@@ -0,0 +1,163 @@
+//! Traces of the commands run, as `set -x` does for shells.
+//!
+//! Each child spawned is told as a line of shell, prefixed with `+` and
+//! the number of its run, with its directory and environment settings, and
+//! where it was made, if known:
+//!
+//! ```text
+//! + [3] cd /srv && LC_ALL=C ls -l 'my dir'  # deploy at ./deploy.ds:12:5
+//! + [3] exit 0 after 1.52ms
+//! ```
+use {
+    super::{
+        events::{self, Event},
+        te, Error, Result,
+    },
+    std::{
+        ffi::OsStr,
+        fmt::{self, Write as _},
+        fs,
+        io::{self, Write},
+        os::unix::process::ExitStatusExt,
+        path::PathBuf,
+        process::Command,
+        str::FromStr,
+        sync::{Mutex, Once, PoisonError},
+    },
+};
+
+/// Where the trace goes, parsed from `stderr` or a path to append to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trace {
+    Stderr,
+    File(PathBuf),
+}
+
+static TRACE: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+static LISTEN: Once = Once::new();
+
+impl Trace {
+    /// Trace all commands run from now on here, rather than where they
+    /// were traced to before.
+    pub fn start(&self) -> Result<()> {
+        let out: Box<dyn Write + Send> = match self {
+            Self::Stderr => Box::new(io::stderr()),
+            Self::File(path) => Box::new(te!(
+                fs::File::options().create(true).append(true).open(path),
+                "Opening trace file {path:?}"
+            )),
+        };
+        *TRACE.lock().unwrap_or_else(PoisonError::into_inner) = Some(out);
+        LISTEN.call_once(|| events::listen(trace));
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "stderr" => Self::Stderr,
+            path => Self::File(path.into()),
+        })
+    }
+}
+
+fn trace(event: &Event<'_>) {
+    let mut line = String::new();
+    let _ = match event {
+        &Event::Spawn {
+            run,
+            cmd,
+            command,
+            origin,
+            ..
+        } => {
+            let origin = origin.map(|origin| format!("  # {origin}"));
+            writeln!(
+                line,
+                "+ [{run}] {}{}",
+                Shell(cmd, command),
+                origin.unwrap_or_default()
+            )
+        }
+        Event::Exit {
+            run,
+            status,
+            duration,
+            ..
+        } => match (status.code(), status.signal()) {
+            (Some(code), _) => writeln!(line, "+ [{run}] exit {code} after {duration:.2?}"),
+            (_, signal) => writeln!(
+                line,
+                "+ [{run}] killed by signal {} after {duration:.2?}",
+                signal.unwrap_or_default()
+            ),
+        },
+        Event::Pipe { .. }
+        | Event::Collect { .. }
+        | Event::Output { .. }
+        | Event::Call { .. }
+        | Event::Return { .. } => return,
+    };
+
+    let mut trace = TRACE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(out) = trace.as_mut() {
+        let _ = out.write_all(line.as_bytes()).and_then(|()| out.flush());
+    }
+}
+
+/// A command, invoked by the name given, as a line of shell.
+struct Shell<'a>(&'a Command, &'a OsStr);
+
+impl fmt::Display for Shell<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(cmd, command) = *self;
+        if let Some(dir) = cmd.get_current_dir() {
+            write!(f, "cd {} && ", Quoted(&dir.to_string_lossy()))?;
+        }
+        for (name, value) in cmd.get_envs() {
+            let name = name.to_string_lossy();
+            match value {
+                Some(value) => write!(f, "{name}={} ", Quoted(&value.to_string_lossy()))?,
+                None => write!(f, "env -u {name} ")?,
+            }
+        }
+        write!(f, "{}", Quoted(&command.to_string_lossy()))?;
+        for arg in cmd.get_args() {
+            write!(f, " {}", Quoted(&arg.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+}
+
+/// A word quoted for shells, if it needs to be, on one line.
+struct Quoted<'a>(&'a str);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let word = self.0;
+        let plain = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c);
+        if !word.is_empty() && word.chars().all(plain) {
+            return f.write_str(word);
+        }
+        if !word.contains(char::is_control) {
+            return write!(f, "'{}'", word.replace('\'', r"'\''"));
+        }
+        // Control characters are escaped, as bash and zsh read them
+        f.write_str("$'")?;
+        for c in word.chars() {
+            match c {
+                '\n' => f.write_str(r"\n")?,
+                '\t' => f.write_str(r"\t")?,
+                '\r' => f.write_str(r"\r")?,
+                '\\' | '\'' => write!(f, "\\{c}")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('\'')
+    }
+}
@@ -79,9 +79,9 @@ lexpop![
         either('{', either('}',
         either('"', either(';', either(',',
         either(exact("def"), either(exact("src"), either(exact("let"),
-        either(exact("val"), either(exact("if"),
-        exact("include")
-    )))))))))))))))))))))))
+        either(exact("val"), either(exact("if"), either(exact("extern"),
+        either(exact("pub"), exact("include"))
+    ))))))))))))))))))))))))
 ];
 
 pub const TOK_NADA: Tok<'static> = Tok::Nada(Nada(""));
@@ -191,6 +191,12 @@ impl<'i> LexState<'i> {
         Self { inp, pos: 0 }
     }
 
+    /// How far the input is lexed. Once lexing is over, this is where a
+    /// character that starts no token stopped it, if short of the end.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
     fn mtch<M, C, T>(&mut self, mut matcher: M, ctor: C) -> Option<Spanned<Tok<'i>>>
     where
         M: lexpop::Prop,
@@ -244,10 +250,11 @@ where
     move |x| !pred(x)
 }
 
-impl<'i> AsRef<str> for Tok<'i> {
-    fn as_ref(&self) -> &str {
+impl<'i> Tok<'i> {
+    /// The text of the token, as a slice of the input.
+    pub fn text(&self) -> &'i str {
         use Tok as t;
-        match self {
+        match *self {
             t::Nada(Nada(s))
             | t::Natural(Natural(s))
             | t::RawString(RawString(s))
@@ -263,3 +270,9 @@ impl<'i> AsRef<str> for Tok<'i> {
         }
     }
 }
+
+impl<'i> AsRef<str> for Tok<'i> {
+    fn as_ref(&self) -> &str {
+        self.text()
+    }
+}
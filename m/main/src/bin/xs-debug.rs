@@ -20,7 +20,9 @@ fn main() -> Result<()> {
     };
 
     let mut bugger = te!(vm::debugger::Bugger::open());
-    let mut compiler = te!(main::load_compiler(&input_path));
+    // Keep the object as is, for the script to see as its own
+    let object = te!(std::fs::read(&input_path));
+    let mut compiler = te!(main::read_compiler(object.as_slice()));
     // let icode = te!(main::load_icode(&input_path));
     let mut vm = te!(main::make_vm());
     let icode = compiler.icode;
@@ -40,7 +42,7 @@ fn main() -> Result<()> {
     }));
 
     te!(vm.init(args));
-    bugger = te!(vm.debug_icode(&icode, bugger));
+    bugger = te!(vm.debug_object(&icode, vm::ScriptObject::Bytes(object), bugger));
 
     te!(te!(bugger
         .receiver_thread
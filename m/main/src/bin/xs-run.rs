@@ -1,9 +1,42 @@
 use {::error::te, main::Result};
 
-fn main() -> Result<()> {
+fn main() {
+    main::errors::main(run)
+}
+
+fn run() -> Result<()> {
     te!(main::init());
 
     let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut verify = true;
+    let mut lossy_utf8 = false;
+    let mut bin_path = None;
+    let mut trace = None;
+    let mut events_to = None;
+    while let Some(opt) = args.first() {
+        match opt.as_str() {
+            // Run the object as is, even if it fails verification
+            "--no-verify" => verify = false,
+            // Use non-UTF-8 outputs as arguments, with U+FFFD replacements
+            "--lossy-utf8" => lossy_utf8 = true,
+            // Find commands in these directories, rather than in PATH
+            "--path" => {
+                args.remove(0);
+                bin_path = Some(te!(args.first().cloned(), "--path without directories"));
+            }
+            // Trace the commands run to stderr, or to a file
+            "--trace" => trace = Some(job::Trace::Stderr),
+            opt if opt.starts_with("--trace=") => {
+                trace = Some(te!(opt["--trace=".len()..].parse::<job::Trace>()));
+            }
+            // Log the events of jobs to a file, as lines of JSON
+            opt if opt.starts_with("--events-to=") => {
+                events_to = Some(opt["--events-to=".len()..].to_owned());
+            }
+            _ => break,
+        }
+        args.remove(0);
+    }
     args.reverse();
 
     let input_path = args.pop();
@@ -19,11 +52,28 @@ fn main() -> Result<()> {
         None => "/dev/stdin".to_owned(),
     };
 
-    let icode = te!(main::load_icode(&input_path));
+    // Keep the object as is, for the script to see as its own
+    let object = te!(std::fs::read(&input_path));
+    let cmp = te!(main::read_compiler(object.as_slice()));
 
     let mut vm = te!(main::make_vm());
+    vm.set_verify(verify);
+    vm.set_lossy_utf8(lossy_utf8);
+    if let Some(bin_path) = bin_path {
+        vm.init_bin_path(bin_path);
+    }
+    if let Some(trace) = trace {
+        te!(trace.start());
+    }
+    if let Some(events_to) = events_to {
+        te!(job::log_events_to(events_to.as_ref()));
+    }
     te!(vm.init(args));
-    te!(vm.eval_icode(&icode));
+    te!(main::eval_script(
+        &mut vm,
+        &cmp,
+        vm::ScriptObject::Bytes(object)
+    ));
 
     #[cfg(feature = "debug")]
     te!(vm.write_to(fs::File::create("./_.vm.txt")));
@@ -1,4 +1,7 @@
-use {::error::te, std::fs};
+use {
+    ::error::te,
+    std::{fs, rc::Rc},
+};
 
 use main::Result;
 
@@ -10,12 +13,13 @@ fn main() -> Result<()> {
     log::debug!("Loading {}", sample_path);
     let sample_text: String = te!(fs::read_to_string(&sample_path));
 
+    let mut cmp = compile::Compiler::new();
+    te!(cmp.init(&sample_path, &sample_text));
+
     log::debug!("Parsing {}", sample_path);
-    let module_ast = te!(parse::parse(&sample_text));
+    let module_ast = te!(cmp.parse(&sample_text));
     log::trace!("AST: {:#?}", module_ast);
 
-    let mut cmp = compile::Compiler::new();
-    te!(cmp.init(&sample_path));
     te!(cmp.compile(module_ast));
 
     #[cfg(not(feature = "release"))]
@@ -24,16 +28,20 @@ fn main() -> Result<()> {
         te!(cmp.write_to(fs::File::create("_.compiler.txt")));
     }
 
-    let icode = if cfg!(release) {
-        cmp.icode
+    let cmp = Rc::new(if cfg!(release) {
+        cmp
     } else {
         // Try out ser-deser, to catch breaks
-        te!(main::sd::copy(&cmp)).icode
-    };
+        te!(main::sd::copy(&cmp))
+    });
 
     let mut vm = te!(main::make_vm());
     te!(vm.init(args));
-    te!(vm.eval_icode(&icode));
+    te!(main::eval_script(
+        &mut vm,
+        &cmp,
+        main::lazy_object(Rc::clone(&cmp))
+    ));
     #[cfg(not(feature = "release"))]
     te!(vm.write_to(fs::File::create("./_.vm.txt")));
 
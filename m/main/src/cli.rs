@@ -1,5 +1,6 @@
 use super::*;
 use error::temg;
+use std::rc::Rc;
 
 pub trait Cmd: Fn(Vec<String>) -> Result<()> {
     fn revargs(&self) -> impl Cmd {
@@ -18,8 +19,11 @@ dump         [- | IN_PATH.obj] [- | OUT_PATH.txt]
 call         [- | IN_PATH.obj] FUNC_NAME [ARGS...]
 ccall        [- | IN_PATH.src] FUNC_NAME [ARGS...]
 crun         [- | IN_PATH.src] [ARGS...]
-run          [- | IN_PATH.obj] [ARGS...]
-link         [- | OUT_PATH.lib] [- | IN_PATH.obj...]     Generated lib files cannot be `run`.
+run          [--dry-run] [- | IN_PATH.obj] [ARGS...]
+                                        --dry-run prints the commands as JSON lines instead of spawning them.
+link         [--prune] [- | OUT_PATH.lib] [- | IN_PATH.obj...]
+                                        Generated lib files cannot be `run`.
+                                        --prune drops defs unreachable from the exported ones.
 debug-run    [- | IN_PATH.obj] [ARGS...]
 debug-call   [- | IN_PATH.obj] FUNC_NAME [ARGS...]
 debug-ccall  [- | IN_PATH.src] FUNC_NAME [ARGS...]
@@ -35,6 +39,8 @@ mega ::
   --list_funcs_to=dest_path         :: write null-separated-list of global functions to dest_path
   --also_run!=false             -r  :: --dump* and --list* options will not run unless this
   --base_path=/script/path.dust -b  :: use this as base_path for include* directives
+  --prune!=false                -p  :: drop defs unreachable from the script entry point
+  --dry-run!=false              -n  :: print the commands as JSON lines instead of spawning them
 
   input : [ path/script , ... ]
   -- [ script-args ... ]
@@ -109,6 +115,8 @@ pub fn megafront() -> impl Cmd {
             dump_text_to: Option<&'a str>,
             base_path: Option<&'a str>,
             list_funcs_to: Option<&'a str>,
+            prune: bool,
+            dry_run: bool,
             rest_args: Option<usize>,
         }
         let mut opts: Opts = Opts::new();
@@ -131,7 +139,9 @@ pub fn megafront() -> impl Cmd {
 
         fn as_path(s: &str) -> Option<ast::Path> {
             let tokens = lex::Lex::new(s);
-            parse::dust::PathParser::new().parse(tokens).ok()
+            parse::dust::PathParser::new()
+                .parse(&mut vec![], tokens)
+                .ok()
         }
 
         type Set = for<'r> fn(&mut Opts<'r>, usize, &'r str);
@@ -184,6 +194,8 @@ pub fn megafront() -> impl Cmd {
                 Some(("--compile", val)) if val != "false" => opts.compile = true,
                 Some(("--also_run", val)) if val != "false" => opts.also_run = true,
                 Some(("--debug", val)) if val != "false" => opts.debug = true,
+                Some(("--prune", val)) if val != "false" => opts.prune = true,
+                Some(("--dry-run", val)) if val != "false" => opts.dry_run = true,
                 Some(("--debug-do-system-main", val)) if val != "false" => {
                     opts.debug_do_system_main = true
                 }
@@ -201,6 +213,8 @@ pub fn megafront() -> impl Cmd {
                     "-c" => opts.compile = true,
                     "-r" => opts.also_run = true,
                     "-d" => opts.debug = true,
+                    "-p" => opts.prune = true,
+                    "-n" => opts.dry_run = true,
                     "-ds" => opts.debug_do_system_main = true,
                     "-l" => set(call),
                     "-b" => set(base_path),
@@ -257,7 +271,7 @@ pub fn megafront() -> impl Cmd {
         let input_scripts = &input_scripts[..];
         let input_order = &input_order[..];
 
-        let compiler = &te!(match (compile, base_path, input_paths, input_scripts,) {
+        let mut compiler = te!(match (compile, base_path, input_paths, input_scripts,) {
             // ---- Compiling section ----
             (true, _, [input_path], []) => compile_file(input_path),
             (true, base_path, [], []) => compile_input_with_base(stdin(), base_path.unwrap_or(cwd)),
@@ -293,6 +307,11 @@ pub fn megafront() -> impl Cmd {
             (_, _, [], []) => read_compiler(stdin()),
             _ => todo!("{opts:?}"),
         });
+        if opts.prune {
+            compile::prune::prune(&mut compiler, compile::prune::Roots::Entry);
+        }
+        let cmp = compiler;
+        let compiler = &cmp;
 
         fn try_dest(
             opt: &Option<&str>,
@@ -328,21 +347,15 @@ pub fn megafront() -> impl Cmd {
         }
 
         let vm: &mut vm::Vm = &mut te!(make_vm());
-        let cmp: &compile::Compiler = compiler;
+        vm.set_dry_run(opts.dry_run);
         let revargs = opts.rest_args(&revargs[..]).rev();
 
         Ok(if let Some(func_addr) = opts.call {
-            te!(make_vm_call2(
-                vm,
-                cmp.to_owned(),
-                func_addr,
-                revargs,
-                opts.debug
-            ))
+            te!(make_vm_call2(vm, cmp, func_addr, revargs, opts.debug))
         } else {
             te!(run_vm_script(
                 vm,
-                cmp,
+                Rc::new(cmp),
                 revargs,
                 (opts.debug, opts.debug_do_system_main)
             ))
@@ -352,12 +365,19 @@ pub fn megafront() -> impl Cmd {
 
 pub fn link() -> impl Cmd {
     |revargs| {
-        let args = |n| args(&revargs, n);
+        let prune = args(&revargs, 1).next().map(String::as_str) == Some("--prune");
+        let args = |n| args(&revargs, n + prune as usize);
 
         let modules: Result<Vec<_>> = args(2).map(|path| load_compiler(&path)).collect();
         let modules = te!(modules);
 
-        let module = compile::link::link_modules(modules);
+        let mut module = te!(compile::link::link_modules(modules));
+        if prune {
+            compile::prune::prune(&mut module, compile::prune::Roots::Exports);
+        }
+        for name in module.relocs.unresolved() {
+            error::lwarn!("Unresolved external reference: {}", name);
+        }
 
         let mut output = te!(args_get_output(args(1)));
         te!(sd::ser(&mut output, &module));
@@ -367,13 +387,16 @@ pub fn link() -> impl Cmd {
 }
 pub fn run() -> impl Cmd {
     |revargs| {
-        let args = |n| args(&revargs, n);
+        let dry_run = args(&revargs, 1).next().map(String::as_str) == Some("--dry-run");
+        let args = |n| args(&revargs, n + dry_run as usize);
 
         let input = te!(args_get_input(args(1)));
 
+        let mut vm = te!(make_vm());
+        vm.set_dry_run(dry_run);
         Ok(te!(run_vm_script(
-            &mut te!(make_vm()),
-            &te!(read_compiler(input)),
+            &mut vm,
+            Rc::new(te!(read_compiler(input))),
             args(2),
             (false, false)
         )))
@@ -388,7 +411,7 @@ pub fn compile_and_run() -> impl Cmd {
 
         Ok(te!(run_vm_script(
             &mut te!(make_vm()),
-            &te!(compile_from_input(input)),
+            Rc::new(te!(compile_from_input(input))),
             args(2),
             (false, false)
         )))
@@ -432,7 +455,7 @@ pub fn debug_run() -> impl Cmd {
 
         Ok(te!(run_vm_script(
             &mut te!(make_vm()),
-            &te!(read_compiler(input)),
+            Rc::new(te!(read_compiler(input))),
             args(2),
             (true, false)
         )))
@@ -553,7 +576,26 @@ pub fn call() -> impl Cmd {
 // [!!] The origincal xs-compile. Do not alter.
 //
 pub fn compile() -> impl Cmd {
-    |args| {
+    |mut args| {
+        const MAX_ERRORS: &str = "--max-errors=";
+        let mut max_errors = compile::DEFAULT_MAX_ERRORS;
+        if let Some(idx) = args.iter().position(|arg| arg.starts_with(MAX_ERRORS)) {
+            let arg = args.remove(idx);
+            max_errors = te!(
+                arg[MAX_ERRORS.len()..].parse().ok(),
+                "Not a number of errors: {}",
+                arg
+            );
+        }
+        let lints = te!(take_lint_flags(&mut args));
+        let optimize = match args.iter().position(|arg| arg == "-O") {
+            Some(idx) => {
+                args.remove(idx);
+                true
+            }
+            None => false,
+        };
+
         let input_path = args.get(1).map(String::as_str).unwrap_or("-");
         let output_path = args.get(2).map(String::as_str).unwrap_or("-");
 
@@ -568,8 +610,13 @@ pub fn compile() -> impl Cmd {
             input_path
         );
 
+        let mut cmp = compile::Compiler::new();
+        cmp.max_errors = max_errors;
+        cmp.lints = lints;
+        te!(cmp.init(&input_path, &input_text));
+
         log::info!("Parsing {}", input_path);
-        let module_ast = te!(parse::parse(&input_text));
+        let module_ast = te!(cmp.parse(&input_text));
         #[cfg(not(feature = "release"))]
         {
             use io::Write;
@@ -577,11 +624,21 @@ pub fn compile() -> impl Cmd {
         }
 
         log::info!("Compiling {}", input_path);
-        let mut cmp = compile::Compiler::new();
-        te!(cmp.init(&input_path));
         te!(cmp
             .compile(module_ast)
             .map_err(|err| err.with_comment(format!("Compiling {}", input_path))));
+        errors::show_warnings(&cmp);
+        if optimize {
+            let compile::peephole::Info {
+                number_of_folded_instructions,
+                number_of_dropped_instructions,
+            } = compile::peephole::optimize(&mut cmp);
+            log::info!(
+                "Optimized: {} instructions folded, {} dropped",
+                number_of_folded_instructions,
+                number_of_dropped_instructions
+            );
+        }
         #[cfg(feature = "debug")]
         {
             use show::Show;
@@ -599,3 +656,34 @@ pub fn compile() -> impl Cmd {
         Ok(())
     }
 }
+
+/// Take the lint flags out of `args`: `-A LINT` to allow a lint, `-W LINT`
+/// to warn about it (`warnings` for all lints), and `--deny-warnings` to
+/// fail on any warning.
+fn take_lint_flags(args: &mut Vec<String>) -> Result<compile::Lints> {
+    let mut lints = compile::Lints::default();
+    let mut idx = 0;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            flag @ ("-A" | "-W") => {
+                let flag = flag.to_owned();
+                args.remove(idx);
+                let lint = te!(
+                    (idx < args.len()).then(|| args.remove(idx)),
+                    "Missing lint after {}",
+                    flag
+                );
+                match flag.as_str() {
+                    "-A" => te!(lints.allow(&lint)),
+                    _ => te!(lints.warn(&lint)),
+                }
+            }
+            "--deny-warnings" => {
+                args.remove(idx);
+                lints.deny = true;
+            }
+            _ => idx += 1,
+        }
+    }
+    Ok(lints)
+}
@@ -1,6 +1,6 @@
 use {
     super::{cli, init, te, Error, ErrorKind, Result},
-    std::{fmt, process},
+    std::fmt,
 };
 
 pub fn main_app(main_app: impl cli::Cmd) {
@@ -16,12 +16,36 @@ pub fn main<M>(main_app: M)
 where
     M: FnOnce() -> Result<()>,
 {
-    match main_app() {
+    let code = match main_app() {
         Ok(_) => return,
-        Err(err) => show_error(err),
-    }
+        Err(err) => {
+            let code = exit_code(&err);
+            show_error(err);
+            code
+        }
+    };
 
-    process::exit(1);
+    job::exit(code);
+}
+
+/// A runtime error of a script, whose dusk backtrace was shown (see
+/// [show_backtrace]).
+#[derive(Debug)]
+pub struct Runtime(pub vm::Error);
+
+/// The exit status of a failure: 124 for a job that timed out, as of
+/// timeout(1), and 1 otherwise.
+pub fn exit_code(err: &Error) -> i32 {
+    let (ErrorKind::Vm(err) | ErrorKind::Runtime(Runtime(err))) = &err.kind else {
+        return 1;
+    };
+    match err.kind {
+        vm::ErrorKind::Job(job::Error {
+            kind: job::ErrorKind::Timeout(_),
+            ..
+        }) => 124,
+        _ => 1,
+    }
 }
 
 fn show_trace(trace: error::Trace) {
@@ -47,10 +71,89 @@ fn show_error(err: Error) {
         ErrorKind::Compile(err) => show_compile_error(err),
         ErrorKind::Parse(err) => show_parse_error(err),
         ErrorKind::Io(io) => show_message(trace, format_args!("{io:?}")),
+        ErrorKind::Vm(vm::Error {
+            kind: vm::ErrorKind::Verify(err),
+            ..
+        }) => show_message(trace, err),
+        ErrorKind::Vm(vm::Error {
+            kind:
+                vm::ErrorKind::Job(job::Error {
+                    kind: job::ErrorKind::Timeout(err),
+                    ..
+                }),
+            ..
+        }) => show_message(trace, err),
         ErrorKind::Vm(err) => show_message(trace, format_args!("{err:?}")),
+        ErrorKind::Runtime(Runtime(err)) => show_runtime_error(err),
         kind @ ErrorKind::None(()) => eprintln!("{:?}", Error { kind, trace }),
         //ErrorKind::None(()) => show_message(trace, format_args!("None Option")),
-        other => panic!("{:?}", other),
+        other => show_message(trace, format_args!("{other:?}")),
+    }
+}
+
+/// Show the dusk call chain of a failed script, and the command that
+/// failed, if any.
+pub fn show_backtrace(vm: &mut vm::Vm, cmp: &compile::Compiler) {
+    let mut frames = vm.backtrace();
+    // The frame calling into main has no source: main is the top level
+    if let Some(vm::backtrace::Frame {
+        func_addr: None, ..
+    }) = frames.last()
+    {
+        frames.pop();
+    }
+    if !frames.is_empty() {
+        eprintln!("Backtrace (most recent call first):");
+    }
+    for (idx, frame) in frames.iter().enumerate() {
+        let name = match frame.func_addr {
+            Some(addr) => match compile::find_func_name(&cmp.sym_table, &addr)
+                .or_else(|| cmp.debug_info.def_name(addr))
+            {
+                Some(vm::SYSTEM_MAIN) => "<top level>",
+                Some(name) => name,
+                None => "<unknown def>",
+            },
+            None => "<syscall>",
+        };
+        match cmp.debug_info.locate(frame.instr) {
+            Some(location) => eprintln!(" {idx:3}: {name} at {location}"),
+            None => eprintln!(" {idx:3}: {name}"),
+        }
+    }
+    if let Some(command) = vm.take_failed_command() {
+        eprintln!("Failed command: {command}");
+        if let Some(cwd) = &command.cwd {
+            eprintln!("  cwd: {cwd}");
+        }
+        for (name, value) in &command.envs {
+            match value {
+                Some(value) => eprintln!("  env: {name}={value}"),
+                None => eprintln!("  env: -{name}"),
+            }
+        }
+    }
+}
+
+/// Show what went wrong in a script, after its backtrace: the message
+/// alone, as the backtrace tells where.
+fn show_runtime_error(err: vm::Error) {
+    let vm::Error { kind, trace } = err;
+    match kind {
+        vm::ErrorKind::Msg(msg) => eprintln!("{msg}"),
+        vm::ErrorKind::Message(msg) => eprintln!("{msg}"),
+        vm::ErrorKind::Io(err) => eprintln!("{err}"),
+        vm::ErrorKind::Verify(err) => eprintln!("{err}"),
+        vm::ErrorKind::Job(job::Error { kind, .. }) => match kind {
+            job::ErrorKind::Msg(msg) => eprintln!("{msg}"),
+            job::ErrorKind::Io(err) => eprintln!("{err}"),
+            job::ErrorKind::Timeout(err) => eprintln!("{err}"),
+            kind => eprintln!("{kind:?}"),
+        },
+        kind => match trace.iter().flat_map(|(_, _, comments)| comments).next() {
+            Some(comment) => eprintln!("{comment}"),
+            None => eprintln!("{kind:?}"),
+        },
     }
 }
 
@@ -58,10 +161,61 @@ fn show_compile_error(err: compile::Error) {
     use compile::ErrorKind;
     let compile::Error { kind, trace } = err;
     match kind {
+        ErrorKind::Diagnostic(diagnostic) => show_diagnostic(trace, *diagnostic),
+        ErrorKind::Diagnostics(diagnostics) => show_diagnostics(trace, diagnostics),
         ErrorKind::ParseDust(err) => show_parse_error(err),
         ErrorKind::Message(msg) => show_message(trace, msg),
         ErrorKind::Io(io) => show_message(trace, format_args!("{:?}", io)),
-        other => panic!("{:?}", other),
+        ErrorKind::Sd2(err) => show_object_error(err),
+        other => show_message(trace, format_args!("{other:?}")),
+    }
+}
+
+/// The source trace of an object that cannot be loaded is only interesting
+/// when debugging the loader itself.
+fn show_object_error(err: buf::sd2::Error) {
+    let buf::sd2::Error { kind, trace } = err;
+    match kind {
+        buf::sd2::ErrorKind::Io(err) => eprintln!("{err}"),
+        buf::sd2::ErrorKind::Utf8(err) => eprintln!("Invalid object string: {err}"),
+        buf::sd2::ErrorKind::Other(msg) => eprintln!("{msg}"),
+        kind @ buf::sd2::ErrorKind::None(()) => eprintln!("{kind:?}"),
+    }
+    if log::log_enabled!(log::Level::Debug) {
+        show_trace(trace);
+    }
+}
+
+/// Show the warnings of a successful compilation.
+pub fn show_warnings(cmp: &compile::Compiler) {
+    for diagnostic in &cmp.diagnostics {
+        eprintln!("{diagnostic:#}");
+    }
+}
+
+/// The source trace of a diagnostic is only interesting when debugging
+/// the compiler itself.
+fn show_diagnostic(trace: error::Trace, diagnostic: error::Diagnostic) {
+    eprint!("{diagnostic:#}");
+    if log::log_enabled!(log::Level::Debug) {
+        show_trace(trace);
+    }
+}
+
+fn show_diagnostics(trace: error::Trace, diagnostics: Vec<error::Diagnostic>) {
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic:#}");
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == error::diagnostic::Severity::Error)
+        .count();
+    match errors {
+        1 => eprintln!("Aborting due to 1 error"),
+        n => eprintln!("Aborting due to {n} errors"),
+    }
+    if log::log_enabled!(log::Level::Debug) {
+        show_trace(trace);
     }
 }
 
@@ -70,6 +224,12 @@ fn show_parse_error(err: parse::Error) {
     let parse::Error { kind, .. } = err;
     match kind {
         ErrorKind::Lalrpop(err) => show_lalrpop_error(err),
+        ErrorKind::Syntax((inp, errs)) => {
+            for err in errs {
+                show_lalrpop_error((inp.clone(), err));
+                eprintln!();
+            }
+        }
         other => panic!("{:?}", other),
     }
 }
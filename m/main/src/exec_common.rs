@@ -9,7 +9,7 @@ pub fn run_main(main: impl FnOnce() -> Result<()>) {
         Ok(r) => r,
         Err(err) => {
             handle_error(err);
-            std::process::exit(1);
+            job::exit(1);
         }
     }
 }
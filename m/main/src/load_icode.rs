@@ -1,7 +1,8 @@
 use {
-    super::{env, fs, io, sd, Result},
-    compile::{ScopeRef, SymbolTableExt},
-    error::{ldebug, te, temg},
+    super::{env, errors, fs, io, sd, Result},
+    compile::SymbolTableExt,
+    error::{ldebug, te, temg, terr},
+    std::rc::Rc,
 };
 
 pub fn load_icode(input_path: &str) -> Result<vm::ICode> {
@@ -14,6 +15,22 @@ pub fn load_compiler(input_path: &str) -> Result<compile::Compiler> {
     Ok(te!(read_compiler(&mut te!(fs::File::open(input_path)))))
 }
 
+/// Serialize `cmp`, as the object of the script it runs (see
+/// [vm::Vm::eval_object]).
+pub fn compiler_object(cmp: &compile::Compiler) -> Result<Vec<u8>> {
+    let mut object = vec![];
+    te!(sd::ser(&mut object, cmp));
+    Ok(object)
+}
+
+/// The object of the script `cmp` runs, serialized only if the script asks
+/// for it.
+pub fn lazy_object(cmp: Rc<compile::Compiler>) -> vm::ScriptObject {
+    vm::ScriptObject::Lazy(Box::new(move || {
+        Ok(te!(compiler_object(&cmp).map_err(|err| format!("{err:?}"))))
+    }))
+}
+
 /// Blantly read compiler serialized data from input stream.
 pub fn read_compiler<R: io::Read>(mut input: R) -> Result<compile::Compiler> {
     let inp: Vec<u8> = {
@@ -76,46 +93,80 @@ pub fn compile_file(input_path: &str) -> Result<compile::Compiler> {
 /// Compile text reading from `input`, using `base_path` as the compilation base path.
 pub fn compile_input_with_base(input: impl io::Read, base_path: &str) -> Result<compile::Compiler> {
     let input_text = te!(io::read_to_string(input));
-    let module_ast =
-        te!(parse::parse(&input_text)
-            .map_err(|err| err.with_comment(format!("Parsing: {base_path}"))));
     let mut compiler = compile::Compiler::new();
-    te!(compiler.init(base_path));
+    te!(compiler.init(base_path, &input_text));
+    let module_ast = te!(compiler
+        .parse(&input_text)
+        .map_err(|err| err.with_comment(format!("Parsing: {base_path}"))));
     te!(compiler
         .compile(module_ast)
         .map_err(|err| err.with_comment(format!("Compiling: {base_path}"))));
+    crate::errors::show_warnings(&compiler);
     Ok(compiler)
 }
 
+/// Run the script compiled in `cmp`, with `object` as its compiled object
+/// (see [vm::Vm::eval_object]). On failure, the dusk backtrace is shown.
+pub fn eval_script(
+    vm: &mut vm::Vm,
+    cmp: &compile::Compiler,
+    object: vm::ScriptObject,
+) -> Result<()> {
+    vm.set_debug_info(cmp.debug_info.clone());
+    if let Err(err) = vm.eval_object(&cmp.icode, object) {
+        errors::show_backtrace(vm, cmp);
+        terr!(errors::Runtime(err))
+    }
+    Ok(())
+}
+
 pub fn make_vm() -> Result<vm::Vm> {
     let mut vm = vm::Vm::default();
     vm.reset();
     te!(vm.init_bin_path_from_path_env());
+    te!(vm.init_settings_from_env());
+    vm.set_library_loader(load_library);
     Ok(vm)
 }
 
+/// The vm's [vm::library::Loader]: load a compiled module, of which the
+/// exported defs can be called.
+pub fn load_library(input_path: &str) -> vm::Result<vm::Library> {
+    let load = || -> Result<vm::Library> {
+        let cmp = te!(load_compiler(input_path));
+        te!(compile::link::ensure_resolved(&cmp));
+        let defs = compile::link::exports(&cmp)
+            .map(|(name, addr)| (name.to_owned(), addr))
+            .collect();
+        Ok(vm::Library::new(input_path.to_owned(), cmp.icode, defs))
+    };
+    Ok(te!(load().map_err(|err| format!("{err:?}"))))
+}
+
 pub fn run_vm_script<T: ExactSizeIterator>(
     vm: &mut vm::Vm,
-    cmp @ compile::Compiler { icode, .. }: &compile::Compiler,
+    cmp: Rc<compile::Compiler>,
     args: impl IntoIterator<IntoIter = T, Item = T::Item>,
     (debug, do_sys_main): (bool, bool),
 ) -> Result<()>
 where
     T::Item: Into<String>,
 {
+    te!(compile::link::ensure_resolved(&*cmp));
     te!(vm.init(args));
     if debug {
         let mut bugger = te!(vm::debugger::Bugger::open());
-        let mut cmp = cmp.to_owned();
+        let object = lazy_object(Rc::clone(&cmp));
+        let mut cmp = (*cmp).clone();
         let icode = std::mem::take(&mut cmp.icode);
         default_debugger_callbacks(&mut bugger, cmp);
         bugger.set_skip_system_main(!do_sys_main);
-        te!(te!(te!(vm.debug_icode(&icode, bugger))
+        te!(te!(te!(vm.debug_object(&icode, object, bugger))
             .receiver_thread
             .join()
             .map_err(|_| format!("Wait receiver thread"))));
     } else {
-        te!(vm.eval_icode(icode));
+        te!(eval_script(vm, &cmp, lazy_object(Rc::clone(&cmp))));
     }
     Ok(())
 }
@@ -150,6 +201,7 @@ where
     Args::IntoIter: ExactSizeIterator,
     Args::Item: Into<String>,
 {
+    te!(compile::link::ensure_resolved(&cmp));
     let sinfo = cmp.lookup_by_name_everywhere(func_name).ok();
     Ok(match sinfo {
         None => temg!("Function not found: {}", func_name),
@@ -171,17 +223,19 @@ where
 
             if debug {
                 let mut bugger = te!(vm::debugger::Bugger::open());
+                let object = lazy_object(Rc::new(cmp.clone()));
                 let icode = std::mem::take(&mut cmp.icode);
                 default_debugger_callbacks(&mut bugger, cmp);
                 bugger.set_in_main();
-                let afterbugger = te!(vm.debug_icode(&icode, bugger));
+                let afterbugger = te!(vm.debug_object(&icode, object, bugger));
                 error::ltrace!("Waiting for afterbugger...");
                 te!(te!(afterbugger
                     .receiver_thread
                     .join()
                     .map_err(|_| format!("Wait receiver thread"))));
             } else {
-                te!(vm.eval_icode(&cmp.icode));
+                let cmp = Rc::new(cmp);
+                te!(eval_script(vm, &cmp, lazy_object(Rc::clone(&cmp))));
             }
             te!(vm::Instr::CleanUp(0).operate_on(vm));
         }
@@ -204,7 +258,7 @@ pub fn default_debugger_callbacks(bugger: &mut vm::debugger::Bugger, compiler: c
 }
 
 pub fn script_call_getret(
-    cmp: &compile::Compiler,
+    cmp: &Rc<compile::Compiler>,
     func_addr: &str,
     args: Vec<String>,
 ) -> Result<Vec<u8>> {
@@ -217,7 +271,7 @@ pub fn script_call_getret(
             let addr = te!(sinfo.as_addr_ref()).addr;
             te!(vm.init(args));
             vm.jump(addr);
-            te!(vm.eval_icode(&cmp.icode));
+            te!(eval_script(vm, cmp, lazy_object(Rc::clone(cmp))));
             //te!(vm::Instr::CleanUp(0).operate_on(vm));
             te!(take_buf(vm))
         }
@@ -231,7 +285,7 @@ pub fn script_call_getret(
             vm::Value::Array(_) => todo!(),
             &vm::Value::Job(vm::value::Job(id)) => match te!(vm.get_job_mut(id)) {
                 job::Job::Null(_) => todo!(),
-                job @ job::Job::Spec(_) => {
+                job @ (job::Job::Spec(_) | job::Job::Hub(_)) => {
                     te!(job.make_buffer());
                     te!(take_buf(vm))
                 }
@@ -245,18 +299,11 @@ pub fn script_call_getret(
     }
 }
 
+/// The exported defs of `cmp` (see [compile::link::exports]).
 pub fn list_func(cmp: &compile::Compiler) -> impl Iterator<Item = &str> {
     use collection::Recollect;
-    cmp.global_scope_opt()
-        .iter()
-        .flat_map(|scope| scope.symbols())
-        .filter_map(|(name, sym_id)| {
-            let info = sym_id.sym_info();
-            if info.scope_id == 1 && !name.starts_with('_') && info.as_addr_ref().is_ok() {
-                return Some(name);
-            }
-            None
-        })
+    compile::link::exports(cmp)
+        .map(|(name, _)| name)
         .sorted()
         .into_iter()
 }
@@ -1,51 +1,108 @@
 pub const VERSION: &str = "0.0.1";
 
 pub use ::lex;
-use lalrpop_util::lalrpop_mod;
+use {
+    error::{te, terr},
+    lalrpop_util::lalrpop_mod,
+};
 lalrpop_mod!(pub dust);
 
 pub type ParseError<'s> = lalrpop_util::ParseError<usize, lex::Tok<'s>, Error>;
 pub type LocationError = lalrpop_util::ParseError<usize, (), ()>;
 pub type SourceError = (String, LocationError);
-error::Error![Lalrpop = SourceError];
+/// Every syntax error found in a source, along with the source.
+pub type SyntaxErrors = (String, Vec<LocationError>);
+/// The errors the parser recovered from.
+pub type Recovered<'s> = Vec<lalrpop_util::ErrorRecovery<usize, lex::Tok<'s>, Error>>;
+error::Error![
+    Lalrpop = SourceError
+    Syntax = SyntaxErrors
+];
 
+/// Parse a module. After a syntax error, parsing resumes from the next
+/// `;`, so that all errors are reported together (as [ErrorKind::Syntax]).
 pub fn parse(source: &str) -> Result<ast::Module> {
-    let inp = lex::Lex::new(source);
-    dust::ModuleParser::new()
-        .parse(inp)
-        .map_err(map_err(source))
+    parse_recovering(source, |errors, inp| {
+        dust::ModuleParser::new().parse(errors, inp)
+    })
 }
 
-// Necessary to kill the input lifetime
-pub fn map_err(inp: &str) -> impl FnOnce(ParseError) -> Error + '_ {
-    |e| {
-        use lalrpop_util::ParseError::*;
-        type L = usize;
-        type T<T> = (L, T, L);
-
-        fn strip(tok: T<lex::Tok>) -> T<()> {
-            let (a, _, b) = tok;
-            (a, (), b)
-        }
+/// Like [parse], for a block of items.
+pub fn parse_block(source: &str) -> Result<ast::Block<'_>> {
+    parse_recovering(source, |errors, inp| {
+        dust::BlockParser::new().parse(errors, inp)
+    })
+}
+
+fn parse_recovering<'s, T>(
+    source: &'s str,
+    parse: impl FnOnce(&mut Recovered<'s>, lex::Lex<'s>) -> std::result::Result<T, ParseError<'s>>,
+) -> Result<T> {
+    let mut recovered = vec![];
+    let result = parse(&mut recovered, lex::Lex::new(source));
 
-        let error = |e: LocationError| -> Error {
-            Error {
-                kind: ErrorKind::Lalrpop((inp.to_owned(), e)),
-                trace: <_>::default(),
-            }
-        };
-
-        match e {
-            InvalidToken { location } => error(InvalidToken { location }),
-            UnrecognizedEof { location, expected } => error(UnrecognizedEof { location, expected }),
-            UnrecognizedToken { token, expected } => error(UnrecognizedToken {
-                token: strip(token),
-                expected,
-            }),
-            ExtraToken { token } => error(ExtraToken {
-                token: strip(token),
-            }),
-            User { error } => error,
+    let mut errors = vec![];
+    for lalrpop_util::ErrorRecovery { error, .. } in recovered {
+        errors.push(te!(locate(error)));
+    }
+    let parsed = match result {
+        Ok(t) => Some(t),
+        Err(error) => {
+            errors.push(te!(locate(error)));
+            None
         }
+    };
+
+    // The lexer ends the tokens at a character that starts none, which the
+    // parser takes for the end of the source
+    let mut lex = lex::Lex::new(source);
+    lex.by_ref().for_each(drop);
+    let location = lex.state.offset();
+    if location < source.len() {
+        use lalrpop_util::ParseError::{InvalidToken, UnrecognizedEof};
+        errors.retain(|error| !matches!(error, UnrecognizedEof { .. }));
+        errors.push(InvalidToken { location });
+    }
+
+    match parsed {
+        Some(t) if errors.is_empty() => Ok(t),
+        _ => terr!((source.to_owned(), errors)),
+    }
+}
+
+/// Strip the tokens off `e`, keeping their locations. Errors from the
+/// lexer are returned as they are.
+fn locate(e: ParseError) -> std::result::Result<LocationError, Error> {
+    use lalrpop_util::ParseError::*;
+    type L = usize;
+    type T<T> = (L, T, L);
+
+    fn strip(tok: T<lex::Tok>) -> T<()> {
+        let (a, _, b) = tok;
+        (a, (), b)
+    }
+
+    Ok(match e {
+        InvalidToken { location } => InvalidToken { location },
+        UnrecognizedEof { location, expected } => UnrecognizedEof { location, expected },
+        UnrecognizedToken { token, expected } => UnrecognizedToken {
+            token: strip(token),
+            expected,
+        },
+        ExtraToken { token } => ExtraToken {
+            token: strip(token),
+        },
+        User { error } => return Err(error),
+    })
+}
+
+// Necessary to kill the input lifetime
+pub fn map_err(inp: &str) -> impl FnOnce(ParseError) -> Error + '_ {
+    |e| match locate(e) {
+        Ok(e) => Error {
+            kind: ErrorKind::Lalrpop((inp.to_owned(), e)),
+            trace: <_>::default(),
+        },
+        Err(error) => error,
     }
 }
@@ -0,0 +1,57 @@
+//! The call chain of a script, as found on the stack.
+//!
+//! Each call frame keeps the frame pointer and instruction to return to,
+//! right below its frame pointer, and its call context (with the call
+//! target) below those. See [Vm::backtrace](crate::Vm::backtrace).
+use std::{fmt, process::Command};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Frame {
+    /// The def running in this frame. `None` for the frame calling into
+    /// the top level of a script ([crate::SYSTEM_MAIN]), and for the frames
+    /// of syscalls.
+    pub func_addr: Option<usize>,
+    /// The instruction this frame is at: the failing one for the innermost
+    /// frame, and the calling one for the rest.
+    pub instr: usize,
+}
+
+/// A command, as given to the process it spawned.
+#[derive(Debug, Default, Clone)]
+pub struct CommandLine {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    /// Environment settings on top of the inherited environment. Removals
+    /// have no value.
+    pub envs: Vec<(String, Option<String>)>,
+}
+
+impl From<&Command> for CommandLine {
+    fn from(cmd: &Command) -> Self {
+        let string = |s: &std::ffi::OsStr| s.to_string_lossy().into_owned();
+        Self {
+            program: string(cmd.get_program()),
+            args: cmd.get_args().map(string).collect(),
+            cwd: cmd.get_current_dir().map(|dir| string(dir.as_os_str())),
+            envs: cmd
+                .get_envs()
+                .map(|(name, value)| (string(name), value.map(string)))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                write!(f, " {arg:?}")?;
+            } else {
+                write!(f, " {arg}")?;
+            }
+        }
+        Ok(())
+    }
+}
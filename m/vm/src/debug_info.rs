@@ -0,0 +1,96 @@
+//! Source locations of instructions.
+//!
+//! The compiler records where each invocation starts in the source, at the
+//! first instruction emitted for it. An instruction is attributed to the
+//! closest recorded location at or before it.
+use std::fmt;
+
+#[derive(Default, Debug, Clone)]
+pub struct DebugInfo {
+    pub files: Vec<String>,
+    /// Sorted by instruction.
+    pub locations: Vec<Location>,
+    /// The name of every def, by address. Unlike the symbol table, this
+    /// keeps nested and non-exported defs.
+    pub defs: Vec<(usize, String)>,
+}
+buf::sd_struct![DebugInfo, files, locations, defs];
+
+#[derive(Default, Debug, Copy, Eq, PartialEq, Clone)]
+pub struct Location {
+    pub instr: usize,
+    /// Index in [DebugInfo::files].
+    pub file: usize,
+    pub line: usize,
+    pub column: usize,
+}
+buf::sd_struct![Location, instr, file, line, column];
+
+/// A [Location] resolved to its file path, for display.
+pub struct SourceLocation<'d> {
+    pub file: &'d str,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl DebugInfo {
+    /// The index of `path` in the file list, adding it if missing.
+    pub fn add_file(&mut self, path: &str) -> usize {
+        match self.files.iter().position(|file| file == path) {
+            Some(file) => file,
+            None => {
+                self.files.push(path.to_owned());
+                self.files.len() - 1
+            }
+        }
+    }
+
+    /// Record `location`, which must not be before any recorded one. A
+    /// later location for the same instruction replaces the earlier.
+    pub fn add_location(&mut self, location: Location) {
+        match self.locations.last_mut() {
+            Some(last) if last.instr == location.instr => *last = location,
+            _ => self.locations.push(location),
+        }
+    }
+
+    pub fn locate(&self, instr: usize) -> Option<SourceLocation<'_>> {
+        let idx = self.locations.partition_point(|loc| loc.instr <= instr);
+        let &Location {
+            file, line, column, ..
+        } = self.locations.get(idx.checked_sub(1)?)?;
+        Some(SourceLocation {
+            file: self.files.get(file)?,
+            line,
+            column,
+        })
+    }
+
+    pub fn def_name(&self, addr: usize) -> Option<&str> {
+        self.defs
+            .iter()
+            .find(|&&(def, _)| def == addr)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Append `other`, whose instructions start at `instr_base`.
+    pub fn append(&mut self, other: &DebugInfo, instr_base: usize) {
+        for location in &other.locations {
+            let file = self.add_file(&other.files[location.file]);
+            self.add_location(Location {
+                instr: location.instr + instr_base,
+                file,
+                ..*location
+            });
+        }
+        for (addr, name) in &other.defs {
+            self.defs.push((addr + instr_base, name.to_owned()));
+        }
+    }
+}
+
+impl<'d> fmt::Display for SourceLocation<'d> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
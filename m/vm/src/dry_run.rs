@@ -0,0 +1,86 @@
+//! Dry runs, printing the commands a script would spawn rather than
+//! spawning them.
+//!
+//! Each command is printed to stdout as a line of JSON, fully expanded:
+//!
+//! ```text
+//! {"job":2,"command":"sed","program":"/usr/bin/sed","args":["s/a/b/"],"cwd":null,"env":{"LC_ALL":"C"},"inputs":["<output of job 1>"]}
+//! ```
+//!
+//! `program` is where the command was found in the bin path, or null, and
+//! `inputs` are the texts fed to it. The output of each command is a stand-in,
+//! `<output of job N>`, wherever it is used in the commands after it.
+use {
+    super::{backtrace::CommandLine, te, Result},
+    job::Json,
+    std::{
+        fmt,
+        io::{self, Write as _},
+        path::Path,
+        process::Command,
+    },
+};
+
+/// The commands printed so far.
+#[derive(Debug, Default)]
+pub struct DryRun {
+    jobs: usize,
+}
+
+struct Record<'a> {
+    job: usize,
+    name: &'a str,
+    program: Option<&'a Path>,
+    line: CommandLine,
+    inputs: &'a [String],
+}
+
+impl DryRun {
+    /// Print `cmd`, invoked as `name` and found as `program`, fed with
+    /// `inputs`, returning the stand-in for its output.
+    pub fn record(
+        &mut self,
+        name: &str,
+        program: Option<&Path>,
+        cmd: &Command,
+        inputs: &[String],
+    ) -> Result<String> {
+        self.jobs += 1;
+        let record = Record {
+            job: self.jobs,
+            name,
+            program,
+            line: cmd.into(),
+            inputs,
+        };
+
+        let mut out = io::stdout().lock();
+        te!(writeln!(out, "{record}"));
+        te!(out.flush());
+        Ok(format!("<output of job {}>", self.jobs))
+    }
+}
+
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            job,
+            name,
+            program,
+            line,
+            inputs,
+        } = self;
+        let program = program.map(|program| program.to_string_lossy());
+
+        write!(f, "{{\"job\":{job},\"command\":{}", Json(*name))?;
+        write!(f, ",\"program\":{}", Json(program.as_deref()))?;
+        write!(f, ",\"args\":{}", Json(&line.args[..]))?;
+        write!(f, ",\"cwd\":{}", Json(line.cwd.as_deref()))?;
+        f.write_str(",\"env\":{")?;
+        for (i, (name, value)) in line.envs.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{sep}{}:{}", Json(name.as_str()), Json(value.as_deref()))?;
+        }
+        write!(f, "}},\"inputs\":{}}}", Json(&inputs[..]))
+    }
+}
@@ -1,7 +1,7 @@
-use std::io;
+use std::io::{self, Read};
 
 use super::{
-    ltrace, soft_todo, syscall, te, terr, value, BorrowMut, Deq, Entry, Job, Map, Result, Vm,
+    ltrace, soft_todo, syscall, te, temg, terr, value, BorrowMut, Deq, Entry, Job, Map, Result, Vm,
 };
 
 fn _use() {
@@ -25,8 +25,12 @@ buf::sd_struct![StringInfo, id];
 
 #[derive(Debug, Copy, Eq, Ord, Hash, PartialEq, PartialOrd, Clone)]
 pub enum Instr {
-    Allocate { size: usize },
-    Jump { addr: usize },
+    Allocate {
+        size: usize,
+    },
+    Jump {
+        addr: usize,
+    },
 
     PushNull,
     PushStr(usize),
@@ -48,6 +52,11 @@ pub enum Instr {
     CleanUp(usize),
     Collect(usize),
     Pipe(usize),
+    /// Pipe a job read by many, through a hub for all its readers.
+    Share {
+        fp_off: usize,
+        readers: usize,
+    },
     BufferString(usize),
 }
 
@@ -86,13 +95,18 @@ impl Instr {
                 te!(vm.push_val(value::SysCallId(id)));
             }
             &Self::Call(_) => {
+                let origin = job::events::listening().then(|| vm.call_site());
                 te!(vm.prepare_call());
                 let addr = te!(vm.call_target_func_addr());
                 vm.jump(addr);
+                vm.called(addr, origin.as_deref());
             }
             &Self::CleanUp(fp_off) => te!(vm.cleanup(fp_off, "", Job::cleanup)),
             &Self::Collect(fp_off) => te!(vm.cleanup(fp_off, "collect", Job::collect)),
             &Self::Pipe(fp_off) => te!(vm.cleanup(fp_off, "pipe", Job::pipe)),
+            &Self::Share { fp_off, readers } => {
+                te!(vm.cleanup(fp_off, "share", |job| job.share(readers)))
+            }
             &Self::BufferString(fp_off) => {
                 log::debug!("Collecting {:?}", te!(vm.frame_get_val(fp_off)));
                 te!(vm.cleanup(fp_off, "string_buffer", |j| -> Result<()> {
@@ -130,91 +144,227 @@ impl StringInfo {
     }
 }
 impl ICode {
+    /// Shift all instruction addresses by `instr_base` and all string ids
+    /// by `str_base`.
+    pub fn relocate(&mut self, instr_base: usize, str_base: usize) {
+        for StringInfo { id } in self.strings.values_mut() {
+            *id += str_base;
+        }
+        for instr in &mut self.instructions {
+            match instr {
+                Instr::PushStr(id) | Instr::RetStr(id) => *id += str_base,
+                Instr::Jump { addr } | Instr::PushFuncAddr(addr) | Instr::RetFuncAddr(addr) => {
+                    *addr += instr_base
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Write the code section of an object (see [write_object]): the
+    /// strings, the number of instructions as a varint, and the
+    /// instructions, as an opcode byte and their varint operands, if any.
+    ///
+    /// Varints are unsigned LEB128.
     pub fn write_to<O>(&self, out: io::Result<O>) -> io::Result<()>
     where
         O: io::Write,
     {
-        let ilen = usize::to_le_bytes(self.instructions.len());
-        out.and_then(|mut out| {
-            buf::sd2::WriteOut::write_out(&self.strings, &mut out)?;
-            out.write_all(&ilen)?;
+        out.and_then(|mut code| {
+            buf::sd2::WriteOut::write_out(&self.strings, &mut code)?;
+            write_varint(&mut code, self.instructions.len())?;
             for instr in &self.instructions {
-                let (code, arg0) = match *instr {
-                    Instr::Allocate { size } => (0x00, size),
-                    Instr::Jump { addr } => (0x01, addr),
-                    Instr::Return(sp_off) => (0x02, sp_off),
-                    Instr::PushNull => (0x03, 0x00),
-                    Instr::PushStr(strid) => (0x04, strid),
-                    Instr::PushNat(val) => (0x05, val),
-                    Instr::Syscall(id) => (0x06, id),
-                    Instr::RetLocal(src_fp_off) => (0x07, src_fp_off),
-                    Instr::PushArgs => (0x08, 0x00),
-                    Instr::PushLocal(fp_off) => (0x09, fp_off),
-                    Instr::Call(addr) => (0x0a, addr),
-                    Instr::CleanUp(fp_off) => (0x0b, fp_off),
-                    Instr::Collect(fp_off) => (0x0c, fp_off),
-                    Instr::PushFuncAddr(addr) => (0x0d, addr),
-                    Instr::Pipe(addr) => (0x0e, addr),
-                    Instr::RetStr(id) => (0x0f, id),
-                    Instr::RetNat(val) => (0x10, val),
-                    Instr::RetFuncAddr(addr) => (0x11, addr),
-                    Instr::PushSysCall(id) => (0x12, id),
-                    Instr::BufferString(fp_off) => (0x13, fp_off),
-                };
-                let code = u8::to_le_bytes(code);
-                let arg = usize::to_le_bytes(arg0);
-                out.write_all(&code)?;
-                out.write_all(&arg)?;
+                let (opcode, operands) = instr.encode();
+                code.write_all(&[opcode])?;
+                for operand in operands {
+                    write_varint(&mut code, operand)?;
+                }
             }
             Ok(())
         })
     }
+    /// Read the code section written by [Self::write_to].
     pub fn load_from<I>(inp: io::Result<I>) -> Result<Self>
     where
         I: io::Read,
     {
-        let mut usize_buf = usize::to_le_bytes(0usize);
-        let mut icode = ICode::default();
-        //let mut byte_buf = Vec::new();
-
-        let inp = Ok(te!(inp));
-        inp.and_then(|mut inp| {
-            icode.strings = te!(buf::sd2::ReadIn::read_in(&mut inp));
-
-            te!(inp.read_exact(&mut usize_buf));
-            let ilen = usize::from_le_bytes(usize_buf);
-            icode.instructions.reserve(ilen);
-            for _ in 0..ilen {
-                te!(inp.read_exact(&mut usize_buf[0..1]));
-                let code = u8::from_le_bytes([usize_buf[0]]);
-                te!(inp.read_exact(&mut usize_buf));
-                let val = usize::from_le_bytes(usize_buf);
-                let instr = match code {
-                    0x00 => Instr::Allocate { size: val },
-                    0x01 => Instr::Jump { addr: val },
-                    0x02 => Instr::Return(val),
-                    0x03 => Instr::PushNull,
-                    0x04 => Instr::PushStr(val),
-                    0x05 => Instr::PushNat(val),
-                    0x06 => Instr::Syscall(val),
-                    0x07 => Instr::RetLocal(val),
-                    0x08 => Instr::PushArgs,
-                    0x09 => Instr::PushLocal(val),
-                    0x0a => Instr::Call(val),
-                    0x0b => Instr::CleanUp(val),
-                    0x0c => Instr::Collect(val),
-                    0x0d => Instr::PushFuncAddr(val),
-                    0x0e => Instr::Pipe(val),
-                    0x0f => Instr::RetStr(val),
-                    0x10 => Instr::RetNat(val),
-                    0x11 => Instr::RetFuncAddr(val),
-                    0x12 => Instr::PushSysCall(val),
-                    0x13 => Instr::BufferString(val),
-                    other => panic!("{:?}", other),
-                };
-                icode.instructions.push_back(instr);
-            }
-            Ok(icode)
+        let mut code = te!(inp);
+        let mut icode = ICode {
+            strings: te!(buf::sd2::ReadIn::read_in(&mut code)),
+            ..<_>::default()
+        };
+        let ilen = te!(read_varint(&mut code));
+        for _ in 0..ilen {
+            let mut opcode = [0];
+            te!(code.read_exact(&mut opcode));
+            icode
+                .instructions
+                .push_back(te!(Instr::decode(opcode[0], &mut code)));
+        }
+        Ok(icode)
+    }
+}
+
+impl Instr {
+    /// The opcode of this instruction, and its operands.
+    fn encode(&self) -> (u8, Vec<usize>) {
+        let (opcode, operand) = match *self {
+            Instr::Allocate { size } => (0x00, size),
+            Instr::Jump { addr } => (0x01, addr),
+            Instr::Return(sp_off) => (0x02, sp_off),
+            Instr::PushNull => return (0x03, vec![]),
+            Instr::PushStr(strid) => (0x04, strid),
+            Instr::PushNat(val) => (0x05, val),
+            Instr::Syscall(id) => (0x06, id),
+            Instr::RetLocal(src_fp_off) => (0x07, src_fp_off),
+            Instr::PushArgs => return (0x08, vec![]),
+            Instr::PushLocal(fp_off) => (0x09, fp_off),
+            Instr::Call(addr) => (0x0a, addr),
+            Instr::CleanUp(fp_off) => (0x0b, fp_off),
+            Instr::Collect(fp_off) => (0x0c, fp_off),
+            Instr::PushFuncAddr(addr) => (0x0d, addr),
+            Instr::Pipe(addr) => (0x0e, addr),
+            Instr::RetStr(id) => (0x0f, id),
+            Instr::RetNat(val) => (0x10, val),
+            Instr::RetFuncAddr(addr) => (0x11, addr),
+            Instr::PushSysCall(id) => (0x12, id),
+            Instr::BufferString(fp_off) => (0x13, fp_off),
+            Instr::Share { fp_off, readers } => return (0x14, vec![fp_off, readers]),
+        };
+        (opcode, vec![operand])
+    }
+
+    /// The instruction of `opcode`, reading its operands from `inp`.
+    fn decode<I: io::Read>(opcode: u8, inp: &mut I) -> Result<Self> {
+        let mut operand = || read_varint(inp);
+        Ok(match opcode {
+            0x00 => Instr::Allocate {
+                size: te!(operand()),
+            },
+            0x01 => Instr::Jump {
+                addr: te!(operand()),
+            },
+            0x02 => Instr::Return(te!(operand())),
+            0x03 => Instr::PushNull,
+            0x04 => Instr::PushStr(te!(operand())),
+            0x05 => Instr::PushNat(te!(operand())),
+            0x06 => Instr::Syscall(te!(operand())),
+            0x07 => Instr::RetLocal(te!(operand())),
+            0x08 => Instr::PushArgs,
+            0x09 => Instr::PushLocal(te!(operand())),
+            0x0a => Instr::Call(te!(operand())),
+            0x0b => Instr::CleanUp(te!(operand())),
+            0x0c => Instr::Collect(te!(operand())),
+            0x0d => Instr::PushFuncAddr(te!(operand())),
+            0x0e => Instr::Pipe(te!(operand())),
+            0x0f => Instr::RetStr(te!(operand())),
+            0x10 => Instr::RetNat(te!(operand())),
+            0x11 => Instr::RetFuncAddr(te!(operand())),
+            0x12 => Instr::PushSysCall(te!(operand())),
+            0x13 => Instr::BufferString(te!(operand())),
+            0x14 => Instr::Share {
+                fp_off: te!(operand()),
+                readers: te!(operand()),
+            },
+            other => temg!("Unknown opcode {other:#04x}"),
         })
     }
 }
+
+/// The first bytes of a compiled object. Not a `#`, which starts a hashbang
+/// line to skip.
+pub const MAGIC: [u8; 4] = *b"\0dsk";
+/// The version of the object format, to bump on any change to it, or to
+/// any of its sections.
+pub const FORMAT_VERSION: u16 = 3;
+
+/// Write a compiled object: [MAGIC], the [FORMAT_VERSION] as a
+/// little-endian `u16`, the length of `body` as a varint, its [checksum] as
+/// a little-endian `u32`, and `body`, the sections of the object.
+pub fn write_object<O: io::Write>(out: &mut O, body: &[u8]) -> io::Result<()> {
+    out.write_all(&MAGIC)?;
+    out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    write_varint(out, body.len())?;
+    out.write_all(&checksum(body).to_le_bytes())?;
+    out.write_all(body)
+}
+
+/// Read a compiled object written by [write_object], and return its body.
+pub fn read_object<I: io::Read>(inp: &mut I) -> io::Result<Vec<u8>> {
+    let mut magic = [0; MAGIC.len()];
+    inp.read_exact(&mut magic)
+        .map_err(at_eof("Not a compiled dusk object"))?;
+    if magic != MAGIC {
+        return Err(invalid_object("Not a compiled dusk object"));
+    }
+    let mut version = [0; 2];
+    inp.read_exact(&mut version)
+        .map_err(at_eof("Truncated object"))?;
+    let version = u16::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(invalid_object(format!(
+            "Object format version {version} is not supported (expected \
+             {FORMAT_VERSION}); compile the script again"
+        )));
+    }
+    let len = read_varint(inp).map_err(at_eof("Truncated object"))?;
+    let mut sum = [0; 4];
+    inp.read_exact(&mut sum)
+        .map_err(at_eof("Truncated object"))?;
+    let mut body = vec![];
+    inp.take(len as u64).read_to_end(&mut body)?;
+    if body.len() != len {
+        return Err(invalid_object("Truncated object"));
+    }
+    if checksum(&body) != u32::from_le_bytes(sum) {
+        return Err(invalid_object("Corrupted object: checksum mismatch"));
+    }
+    Ok(body)
+}
+
+fn invalid_object(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Tell the end of the input as `msg`, rather than as an I/O error.
+fn at_eof(msg: &'static str) -> impl Fn(io::Error) -> io::Error {
+    move |err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => invalid_object(msg),
+        _ => err,
+    }
+}
+
+/// The FNV-1a hash of `bytes`.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn write_varint<O: io::Write>(out: &mut O, mut val: usize) -> io::Result<()> {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+        if val == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<I: io::Read>(inp: &mut I) -> io::Result<usize> {
+    let mut val = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let mut byte = [0];
+        inp.read_exact(&mut byte)?;
+        let bits = (byte[0] & 0x7f) as usize;
+        if bits.leading_zeros() < shift {
+            break;
+        }
+        val |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(val);
+        }
+    }
+    Err(invalid_object("Varint overflow"))
+}
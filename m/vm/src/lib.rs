@@ -1,13 +1,23 @@
 pub const VERSION: &str = "0.0.1";
+pub mod backtrace;
+pub mod debug_info;
 pub mod debugger;
+pub mod dry_run;
 pub mod icode;
+pub mod library;
 pub mod syscall;
 pub mod to_sh;
 pub mod value;
+pub mod verify;
 pub use {
+    debug_info::DebugInfo,
     icode::{ICode, Instr, Instrs, StringInfo, Strings},
+    library::Library,
     value::{Value, ValueTypeInfo},
-    vm::{Vm, DEBUG_STACK_SIZE},
+    vm::{
+        parse_setting, ScriptObject, Vm, DEBUG_STACK_SIZE, SYSTEM_MAIN, TEE_ENV, TIMEOUT_ENV,
+        TRACE_ENV,
+    },
 };
 
 mod vm;
@@ -30,6 +40,7 @@ error::Error! {
     Fmt = std::fmt::Error
     BufferSd = buf::sd2::Error
     ParseInt = std::num::ParseIntError
+    Verify = verify::Error
 }
 use error::{ltrace, soft_todo};
 use error::{te, temg, terr};
@@ -0,0 +1,43 @@
+//! Compiled modules loaded into a running [Vm](crate::Vm).
+//!
+//! The vm knows nothing about the serialized compiler format, so reading a
+//! module is delegated to a [Loader], set up by the embedding program. A
+//! loaded library gets its own address range, starting at [BASE], and its
+//! strings are appended to the vm's string table.
+use super::{ICode, Map, Result};
+
+/// Start of the address space of loaded libraries. Script addresses are
+/// always below this.
+pub const BASE: usize = usize::MAX / 2;
+
+/// Read the module at the given path.
+pub type Loader = fn(&str) -> Result<Library>;
+
+#[derive(Debug)]
+pub struct Library {
+    pub path: String,
+    pub icode: ICode,
+    /// The defs callable from outside, by name.
+    pub defs: Map<String, usize>,
+    pub(crate) base: usize,
+}
+
+impl Library {
+    pub fn new(path: String, icode: ICode, defs: Map<String, usize>) -> Self {
+        Self {
+            path,
+            icode,
+            defs,
+            base: 0,
+        }
+    }
+
+    /// The first address past this library.
+    pub fn end(&self) -> usize {
+        self.base + self.icode.instructions.len()
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        (self.base..self.end()).contains(&addr)
+    }
+}
@@ -64,8 +64,7 @@ where
             inject(te!(from_utf8(subsl)));
         }
         &Value::Job(value::Job(jobid)) => {
-            let job = te!(vm.get_job_mut(jobid));
-            inject(te!(job.make_string()));
+            inject(&te!(vm.job_string(jobid)));
         }
         &Value::DynString(value::DynString(string_id)) => {
             let string = te!(vm.get_dynstring_id(string_id));
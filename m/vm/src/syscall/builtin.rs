@@ -1,6 +1,6 @@
 use {
     super::CallArgs,
-    crate::{te, temg, Result, Value, Vm},
+    crate::{te, temg, value, Result, Value, Vm},
 };
 
 pub fn builtin(vm: &mut Vm) -> Result<()> {
@@ -25,6 +25,8 @@ pub fn builtin(vm: &mut Vm) -> Result<()> {
 
     let retval: Value = te!(match builtin_name.as_str() {
         "__lib" => GET_VM_ICODE(vm),
+        "load" => LOAD_LIBRARY(vm),
+        "call" => CALL_LIBRARY(vm),
         other => temg!("Unknown builtin: {other}"),
     });
 
@@ -59,10 +61,43 @@ impl<'s> BuiltinArgs<'s> {
 
 type SysCall = fn(&mut Vm) -> Result<Value>;
 
+/// `__builtin __lib`: the compiled object of the running script.
 const GET_VM_ICODE: SysCall = |vm| {
-    let script = te!(vm.current_script_value());
-    let val: Value = script.to_owned();
-    Ok(val)
+    // A copy per call, since the caller consumes the buffer
+    let object: Vec<u8> = te!(vm.current_script_data()).to_vec();
+    let job = job::Job::Buffer(job::Buffer::Bytes(
+        std::process::Command::new("<lib>"),
+        object,
+    ));
+    Ok(value::Job(vm.add_job(job)).into())
+};
+
+/// `__builtin load PATH`: load a compiled module and return its handle.
+const LOAD_LIBRARY: SysCall = |vm| {
+    let arg: Value = te!(te!(BuiltinArgs::from_vm(vm)).arg(0), "Missing library path").to_owned();
+    let mut path = String::new();
+    te!(super::inject_val(vm, &arg, &mut |s| path.push_str(s)));
+    let handle = te!(vm.load_library(&path));
+    Ok(handle.into())
+};
+
+/// `__builtin call HANDLE NAME ARGS...`: call a def of a loaded library.
+const CALL_LIBRARY: SysCall = |vm| {
+    let (handle, name, cwd, args) = {
+        let bargs = te!(BuiltinArgs::from_vm(vm));
+        let handle: usize = match te!(bargs.arg(0), "Missing library handle") {
+            Value::Natural(handle) => *handle,
+            other => te!(te!(bargs.0.val_as_str(other)).parse::<usize>()),
+        };
+        let name: String = te!(bargs.arg_str(1), "Missing def name").to_owned();
+        let nargs = te!(bargs.nargs());
+        let args: Vec<Value> = te!((2..nargs - 1)
+            .map(|idx| bargs.arg(idx).cloned())
+            .collect::<Result<_>>());
+        let cwd: Value = bargs.1.cwd.to_owned();
+        (handle, name, cwd, args)
+    };
+    Ok(te!(vm.call_library(handle, &name, cwd, args)))
 };
 
 pub fn to_shell(call_args: CallArgs<&Value>) -> Result<()> {
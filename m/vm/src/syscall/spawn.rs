@@ -1,8 +1,13 @@
 use {
     super::{ldebug, te, temg, value, Job, Result, Value, Vm},
+    crate::{parse_setting, TEE_ENV, TIMEOUT_ENV},
     std::{
         fmt::Write,
+        mem,
+        os::unix::process::CommandExt,
+        path::Path,
         process::{Command, Stdio},
+        str::FromStr,
     },
 };
 
@@ -10,7 +15,7 @@ pub fn spawn(vm: &mut Vm) -> Result<()> {
     te!(vm.prepare_call());
 
     let &nargs: &usize = te!(vm.arg_get(0));
-    let cwd: &Value = te!(vm.arg_get_val(nargs + 1));
+    let cwd: Value = te!(vm.arg_get_val(nargs + 1)).to_owned();
     let target: &Value = te!(vm.arg_get_val(nargs + 2));
     let &inp_redir_n: &usize = te!(vm.arg_get(nargs + 3));
     let &nenvs: &usize = te!(vm.arg_get(nargs + 3 + inp_redir_n + 1));
@@ -33,20 +38,48 @@ pub fn spawn(vm: &mut Vm) -> Result<()> {
         nenvs = nenvs,
     );
 
-    let target: &str = te!(vm.val_as_str(&target));
+    let target: String = te!(vm.val_as_str(&target)).to_owned();
 
-    // Set command name and close stdin
+    // The working directory and the environment of the command, which the
+    // command is found with
     //
-    let mut cmd = Command::new(target);
-    cmd.stdin(Stdio::null());
-
+    let mut dir = None;
     if !cwd.is_null() {
-        let cwd = cwd.to_owned();
         te!(inject_val(vm, &cwd, &mut |cwd| {
             error::ltrace!("cwd = '{}'", cwd);
-            cmd.current_dir(cwd);
+            dir = Some(cwd.to_owned());
         }));
     }
+    let mut envs: Vec<(String, String)> = vec![];
+    {
+        // Values come first, and may be empty
+        let mut value: Option<String> = None;
+        te!(install_args(
+            vm,
+            &mut |env| match value.take() {
+                None => value = Some(env.to_owned()),
+                Some(value) => envs.push((env.to_owned(), value)),
+            },
+            ADDR_ENV,
+            &mut String::new()
+        ));
+    }
+    let path = envs.iter().rev().find(|(env, _)| env == "PATH");
+    let path = path.map(|(_, path)| path.as_str());
+
+    // Set command name, as found in the bin path, and close stdin. A dry run
+    // prints the commands not found as well.
+    //
+    let program = match vm.find_command(&target, path, dir.as_deref().map(Path::new)) {
+        Err(_) if vm.dry_run().is_some() => None,
+        found => Some(te!(found)),
+    };
+    let mut cmd = Command::new(program.as_deref().unwrap_or(Path::new(&target)));
+    cmd.arg0(&target);
+    cmd.stdin(Stdio::null());
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
 
     // Set command args
     //
@@ -59,28 +92,23 @@ pub fn spawn(vm: &mut Vm) -> Result<()> {
         &mut String::new()
     ));
 
-    // Set command environment
+    // Stream the output as it is captured, and bound how long it runs, as
+    // set for this invocation, or else for all of them
     //
-    {
-        let mut value = String::new();
-        te!(install_args(
-            vm,
-            &mut |env| {
-                if value.is_empty() {
-                    value.push_str(env);
-                } else {
-                    cmd.env(env, value.as_str());
-                    value.clear();
-                }
-            },
-            ADDR_ENV,
-            &mut String::new()
-        ));
-    }
+    let tee = te!(invocation_setting(&mut envs, TEE_ENV, vm.tee().cloned()));
+    let timeout = te!(invocation_setting(&mut envs, TIMEOUT_ENV, vm.timeout()));
+    cmd.envs(envs);
 
     // Turn into a job
     //
     let mut job: Job = cmd.into();
+    let spec = te!(job.as_spec_mut());
+    spec.tee = tee;
+    spec.timeout = timeout;
+    if job::events::listening() {
+        spec.origin = Some(vm.call_site());
+        spec.name = Some(target.clone());
+    }
 
     //
     // ## Connect input redirections ##
@@ -139,23 +167,47 @@ pub fn spawn(vm: &mut Vm) -> Result<()> {
         inp_jobs.push(redir_insert)
     }
     //
+    // The text of a string source type.
+    fn string_input(vm: &Vm, typ: &Id, id: usize) -> Result<String> {
+        Ok(match typ {
+            Id::Str => te!(vm.get_string_id(id)).to_owned(),
+            Id::DStr => te!(vm.get_dynstring_id(id)).to_owned(),
+            Id::Nat => format!("{}", id),
+            Id::Null => String::new(),
+            other => temg!("Not a string: {:?}", other),
+        })
+    }
+    //
     // Create a Job from a text string source type. When connected it will
     // output the string value itself.
     fn make_string_input(vm: &Vm, typ: Id, id: usize) -> Result<Job> {
         Ok({
             let cmd = Command::new("<internal string source>");
-            let string = match typ {
-                Id::Str => te!(vm.get_string_id(id)).to_owned(),
-                Id::DStr => te!(vm.get_dynstring_id(id)).to_owned(),
-                Id::Nat => format!("{}", id),
-                Id::Null => String::new(),
-                other => temg!("Not a string: {:?}", other),
-            };
+            let string = te!(string_input(vm, &typ, id));
             let buffer = job::Buffer::String(cmd, string);
             Job::Buffer(buffer)
         })
     }
     //
+    // In a dry run, print the command with the text of each input, and put
+    // a stand-in for its output in place of the job.
+    if vm.dry_run().is_some() {
+        let mut inputs = vec![];
+        for (typ, id) in &inp_jobs {
+            inputs.push(match typ {
+                Id::Job => te!(vm.job_string(*id)).into_owned(),
+                typ => te!(string_input(vm, typ, *id)),
+            });
+        }
+        let Job::Spec(spec) = mem::take(&mut job) else {
+            temg!("Internal error: not a job spec")
+        };
+        let dry_run = te!(vm.dry_run());
+        let output = te!(dry_run.record(&target, program.as_deref(), &spec.cmd, &inputs));
+        job = Job::Buffer(job::Buffer::String(spec.cmd, output));
+        inp_jobs.clear();
+    }
+    //
     // Get a Job object from each (Id, id) defined as input, and add them
     // to the target Job's input list.
     for inp_job0 in inp_jobs {
@@ -197,6 +249,25 @@ pub fn spawn(vm: &mut Vm) -> Result<()> {
     Ok(())
 }
 
+/// The setting `name` of an invocation, made as one of its environment
+/// variables `envs`, or else `all`. The setting is taken out of `envs`, so
+/// that the scripts the invocation runs do not inherit it.
+fn invocation_setting<T>(
+    envs: &mut Vec<(String, String)>,
+    name: &str,
+    all: Option<T>,
+) -> Result<Option<T>>
+where
+    T: FromStr<Err = job::Error>,
+{
+    let Some(idx) = envs.iter().rposition(|(env, _)| env == name) else {
+        return Ok(all);
+    };
+    let (_, setting) = envs.remove(idx);
+    envs.retain(|(env, _)| env != name);
+    Ok(te!(parse_setting(name, setting.as_ref())))
+}
+
 type Addr = fn(&Vm, usize) -> Result<(usize, usize)>;
 
 const ADDR_ARG: Addr = |vm, i| Ok((te!(vm.arg_addr(i)), 1));
@@ -247,8 +318,7 @@ where
             inject(sbuf);
         }
         &Value::Job(value::Job(jobid)) => {
-            let job = te!(vm.get_job_mut(jobid));
-            inject(te!(job.make_string()));
+            inject(&te!(vm.job_string(jobid)));
         }
         &Value::DynString(value::DynString(string_id)) => {
             let string = te!(vm.get_dynstring_id(string_id));
@@ -287,8 +357,7 @@ where
             inject(te!(from_utf8(subsl)));
         }
         &Value::Job(value::Job(jobid)) => {
-            let job = te!(vm.get_job_mut(jobid));
-            inject(te!(job.make_string()));
+            inject(&te!(vm.job_string(jobid)));
         }
         &Value::DynString(value::DynString(string_id)) => {
             let string = te!(vm.get_dynstring_id(string_id));
@@ -0,0 +1,308 @@
+//! Checks of compiled code, before running it.
+//!
+//! The vm trusts its code: a jump out of range, an unknown string or a
+//! frame of the wrong size make it panic, or misread its stack. Code from a
+//! corrupted or hand-edited object is verified first (see
+//! [Vm::load_object](crate::Vm::load_object)).
+//!
+//! The body of a def is straight-line code, starting with an `Allocate` and
+//! ending with a `Return`, where the only jumps skip over nested defs. Each
+//! push takes the next cell of the frame, so the frame has as many cells as
+//! there are pushes in the body.
+use {
+    super::{ICode, Instr},
+    std::{collections::HashSet, fmt},
+};
+
+/// The violations found in some code.
+#[derive(Debug)]
+pub struct Error {
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The address of the offending instruction.
+    pub addr: usize,
+    pub kind: Kind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// A jump to an address past the code.
+    JumpOutOfRange { target: usize },
+    /// A def address that is not the entry of a def.
+    NotADef { target: usize },
+    /// A string id missing from the strings of the code.
+    UnknownString { id: usize },
+    /// A def not starting with an `Allocate`.
+    MissingAllocate,
+    /// A def running past the end of the code.
+    MissingReturn,
+    /// A `Return` of a frame not of the size allocated, or not of the size
+    /// pushed.
+    FrameSize {
+        allocated: usize,
+        pushed: usize,
+        returned: usize,
+    },
+    /// An access to a cell not yet pushed on the frame.
+    CellOutOfFrame { fp_off: usize, pushed: usize },
+}
+
+/// Verify `icode`, returning every violation found.
+pub fn verify(icode: &ICode) -> Result<(), Error> {
+    let instructions = &icode.instructions;
+    let string_ids: HashSet<usize> = icode.strings.values().map(|info| info.id).collect();
+    let mut violations = vec![];
+    let mut violation = |addr, kind| violations.push(Violation { addr, kind });
+
+    // The top level, and the entries of the defs the jumps skip
+    let mut bodies = vec![0];
+    for (addr, instr) in instructions.iter().enumerate() {
+        match *instr {
+            Instr::Jump { addr: target } if target >= instructions.len() => {
+                violation(addr, Kind::JumpOutOfRange { target })
+            }
+            Instr::Jump { addr: target } if target > addr + 1 => bodies.push(addr + 1),
+            Instr::PushStr(id) | Instr::RetStr(id) if !string_ids.contains(&id) => {
+                violation(addr, Kind::UnknownString { id })
+            }
+            _ => (),
+        }
+    }
+    let entries: HashSet<usize> = bodies.iter().copied().collect();
+    for (addr, instr) in instructions.iter().enumerate() {
+        if let Instr::PushFuncAddr(target) | Instr::RetFuncAddr(target) = *instr {
+            if !entries.contains(&target) {
+                violation(addr, Kind::NotADef { target });
+            }
+        }
+    }
+
+    for start in bodies {
+        verify_body(icode, start, &mut violation);
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        violations.sort_by_key(|violation| violation.addr);
+        Err(Error { violations })
+    }
+}
+
+/// Verify the frame of the body starting at `start`.
+fn verify_body(icode: &ICode, start: usize, violation: &mut impl FnMut(usize, Kind)) {
+    let instructions = &icode.instructions;
+    let Some(&Instr::Allocate { size: allocated }) = instructions.get(start) else {
+        return violation(start, Kind::MissingAllocate);
+    };
+    let mut pushed = 0;
+    let mut addr = start + 1;
+    while let Some(&instr) = instructions.get(addr) {
+        match instr {
+            // A nested def, verified on its own
+            Instr::Jump { addr: target } if target > addr + 1 => {
+                addr = target;
+                continue;
+            }
+            Instr::Return(returned) => {
+                if returned != allocated || returned != pushed {
+                    let kind = Kind::FrameSize {
+                        allocated,
+                        pushed,
+                        returned,
+                    };
+                    violation(addr, kind);
+                }
+                return;
+            }
+            Instr::PushNull
+            | Instr::PushStr(_)
+            | Instr::PushNat(_)
+            | Instr::PushFuncAddr(_)
+            | Instr::PushSysCall(_)
+            | Instr::PushArgs => pushed += 1,
+            Instr::PushLocal(fp_off) => {
+                if fp_off >= pushed {
+                    violation(addr, Kind::CellOutOfFrame { fp_off, pushed });
+                }
+                pushed += 1;
+            }
+            Instr::RetLocal(fp_off)
+            | Instr::CleanUp(fp_off)
+            | Instr::Collect(fp_off)
+            | Instr::Pipe(fp_off)
+            | Instr::Share { fp_off, .. }
+            | Instr::BufferString(fp_off)
+                if fp_off >= pushed =>
+            {
+                violation(addr, Kind::CellOutOfFrame { fp_off, pushed })
+            }
+            _ => (),
+        }
+        addr += 1;
+    }
+    violation(start, Kind::MissingReturn)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Invalid code:")?;
+        for violation in &self.violations {
+            writeln!(f, "  {violation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Self { addr, kind } = self;
+        write!(f, "at {addr}: {kind}")
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::JumpOutOfRange { target } => write!(f, "jump to {target}, past the code"),
+            Kind::NotADef { target } => write!(f, "no def at {target}"),
+            Kind::UnknownString { id } => write!(f, "unknown string {id}"),
+            Kind::MissingAllocate => write!(f, "def without an allocation"),
+            Kind::MissingReturn => write!(f, "def without a return"),
+            Kind::FrameSize {
+                allocated,
+                pushed,
+                returned,
+            } => write!(
+                f,
+                "return of {returned} cells, from a frame of {allocated} allocated and \
+                 {pushed} pushed"
+            ),
+            Kind::CellOutOfFrame { fp_off, pushed } => {
+                write!(f, "access to cell {fp_off}, of a frame of {pushed} cells")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{verify, Error, Kind, Violation},
+        crate::{ICode, Instr, StringInfo},
+    };
+
+    /// The violations of `instructions`, with one string of id 0.
+    fn violations<const N: usize>(instructions: [Instr; N]) -> Vec<Violation> {
+        let icode = ICode {
+            instructions: instructions.into_iter().collect(),
+            strings: [("s".to_owned(), StringInfo { id: 0 })]
+                .into_iter()
+                .collect(),
+        };
+        match verify(&icode) {
+            Ok(()) => vec![],
+            Err(Error { violations }) => violations,
+        }
+    }
+
+    fn at(addr: usize, kind: Kind) -> Violation {
+        Violation { addr, kind }
+    }
+
+    #[test]
+    fn valid() {
+        let found = violations([
+            Instr::Allocate { size: 2 },
+            Instr::Jump { addr: 4 },
+            Instr::Allocate { size: 0 },
+            Instr::Return(0),
+            Instr::PushStr(0),
+            Instr::PushFuncAddr(2),
+            Instr::Return(2),
+        ]);
+        assert_eq!(found, vec![]);
+    }
+
+    #[test]
+    fn jump_out_of_range() {
+        let found = violations([
+            Instr::Allocate { size: 0 },
+            Instr::Jump { addr: 9 },
+            Instr::Return(0),
+        ]);
+        // The jump also skips the return
+        assert_eq!(
+            found,
+            vec![
+                at(0, Kind::MissingReturn),
+                at(1, Kind::JumpOutOfRange { target: 9 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn not_a_def() {
+        let found = violations([
+            Instr::Allocate { size: 1 },
+            Instr::PushFuncAddr(1),
+            Instr::Return(1),
+        ]);
+        assert_eq!(found, vec![at(1, Kind::NotADef { target: 1 })]);
+    }
+
+    #[test]
+    fn unknown_string() {
+        let found = violations([
+            Instr::Allocate { size: 1 },
+            Instr::PushStr(1),
+            Instr::Return(1),
+        ]);
+        assert_eq!(found, vec![at(1, Kind::UnknownString { id: 1 })]);
+    }
+
+    #[test]
+    fn missing_allocate() {
+        let found = violations([Instr::PushNull, Instr::Return(1)]);
+        assert_eq!(found, vec![at(0, Kind::MissingAllocate)]);
+    }
+
+    #[test]
+    fn missing_return() {
+        let found = violations([Instr::Allocate { size: 1 }, Instr::PushNull]);
+        assert_eq!(found, vec![at(0, Kind::MissingReturn)]);
+    }
+
+    #[test]
+    fn frame_size() {
+        let found = violations([
+            Instr::Allocate { size: 1 },
+            Instr::PushNull,
+            Instr::PushNull,
+            Instr::Return(1),
+        ]);
+        let kind = Kind::FrameSize {
+            allocated: 1,
+            pushed: 2,
+            returned: 1,
+        };
+        assert_eq!(found, vec![at(3, kind)]);
+    }
+
+    #[test]
+    fn cell_out_of_frame() {
+        let found = violations([
+            Instr::Allocate { size: 1 },
+            Instr::PushLocal(0),
+            Instr::Return(1),
+        ]);
+        let kind = Kind::CellOutOfFrame {
+            fp_off: 0,
+            pushed: 0,
+        };
+        assert_eq!(found, vec![at(1, kind)]);
+    }
+}
@@ -1,16 +1,63 @@
 use {
     super::{
-        debugger::Bugger as Debugger, ltrace, syscall, te, temg, value, Deq, ICode, Job, Result,
-        StringInfo, TryFrom, Value, ValueTypeInfo,
+        backtrace::{CommandLine, Frame},
+        debugger::Bugger as Debugger,
+        dry_run::DryRun,
+        library, ltrace, te, temg, value, verify, DebugInfo, Deq, ICode, Instr, Job, Library,
+        Result, StringInfo, TryFrom, Value, ValueTypeInfo,
+    },
+    job::events::{self, Event},
+    std::{
+        borrow::Cow,
+        collections::HashMap,
+        ffi::OsStr,
+        fmt, io, mem,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        result,
+        str::FromStr,
     },
-    std::{fmt, io, mem, result},
 };
 
 pub const DEBUG_STACK_SIZE: usize = 45;
 
+/// The environment variable streaming the captured outputs, as they come,
+/// to stdout or stderr (see [job::Tee]). An invocation can set it as well,
+/// to stream only its own output, or empty, to stream nothing.
+pub const TEE_ENV: &str = "DUSK_TEE";
+
+/// The environment variable bounding how long each job runs (see
+/// [job::Timeout]). An invocation can set it as well, to bound only its own
+/// run, or empty, to leave it unbounded.
+pub const TIMEOUT_ENV: &str = "DUSK_TIMEOUT";
+
+/// The environment variable tracing the commands run, to stderr or to a file
+/// (see [job::Trace]).
+pub const TRACE_ENV: &str = "DUSK_TRACE";
+
+/// The cells of a call context, besides the args: the return value, the
+/// numbers of env var settings and of input redirections, the invocation
+/// target, the cwd and the number of args.
+const CALL_CONTEXT_SIZE: usize = 6;
+
+/// The cells saved by [Vm::prepare_call] between the call context and the
+/// frame: the caller's frame pointer and return instruction.
+const CALL_STACK_DATA_SIZE: usize = 2;
+
+/// Jobs and dynstrings to add before collecting garbage, at the least. A
+/// collection takes as many more as survived the last one.
+const GC_MIN_ALLOCATIONS: usize = 64;
+
+/// The def the top-level code of a script is compiled into.
+pub const SYSTEM_MAIN: &str = "m___system_main___";
+
 #[derive(Default, Debug)]
 pub struct Vm {
     pub bin_path: Deq<String>,
+    /// Where commands were found in [Self::bin_path], by name.
+    bin_cache: HashMap<String, PathBuf>,
+    /// The names of the programs found, by program.
+    bin_names: HashMap<PathBuf, String>,
     string_table: Deq<String>,
     dynstring_table: Deq<String>,
     job_table: Deq<Job>,
@@ -19,11 +66,44 @@ pub struct Vm {
     stack_ptr: usize,
     instr_ptr: usize,
     debugger: Option<Debugger>,
-    /// A stack of currently executing scripts (ICode).
-    /// The stack stores a stack_addr, per each invocation
-    /// of [Self::load_icode], which points to a Value of the stack,
-    /// which is a of type [job::Job]-[job::Buffer]-[job::Buffer::Bytes].
-    scripts_stack: Vec<usize>,
+    /// The compiled objects of the currently executing scripts, one per
+    /// invocation of [Self::load_object].
+    scripts_stack: Vec<ScriptObject>,
+    /// Libraries loaded with [Self::load_library], in address order. A
+    /// library handle is an index in this list.
+    libraries: Vec<Library>,
+    library_loader: Option<library::Loader>,
+    /// Source locations of the script's instructions, for error messages.
+    debug_info: DebugInfo,
+    /// The command of the last job that failed to clean up, until the next
+    /// one cleans up fine or it is taken.
+    failed_command: Option<CommandLine>,
+    /// Run code without verifying it first (see [verify]).
+    no_verify: bool,
+    /// Replace invalid UTF-8 in outputs used as arguments, rather than fail.
+    lossy_utf8: bool,
+    /// Where to stream the outputs of all jobs, as they are captured.
+    tee: Option<job::Tee>,
+    /// How long all jobs may run.
+    timeout: Option<job::Timeout>,
+    /// Print commands instead of spawning them (see [DryRun]).
+    dry_run: Option<DryRun>,
+    /// Ids of reclaimed jobs, to reuse (see [Self::collect_garbage]).
+    free_jobs: Vec<usize>,
+    /// Ids of reclaimed dynstrings, to reuse.
+    free_dynstrings: Vec<usize>,
+    /// Jobs and dynstrings added since the last collection.
+    allocations: usize,
+    /// Jobs and dynstrings left after the last collection.
+    survivors: usize,
+}
+
+/// The compiled object of a script, as seen by `__builtin __lib`.
+pub enum ScriptObject {
+    /// Written already, as read from a file.
+    Bytes(Vec<u8>),
+    /// Written when first seen, if ever, since writing it costs.
+    Lazy(Box<dyn FnOnce() -> Result<Vec<u8>>>),
 }
 
 pub struct Stack {
@@ -35,6 +115,15 @@ impl Stack {
     // TODO
 }
 
+impl fmt::Debug for ScriptObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bytes(object) => write!(f, "Bytes({} bytes)", object.len()),
+            Self::Lazy(_) => f.write_str("Lazy"),
+        }
+    }
+}
+
 impl Vm {
     pub fn string_table(&self) -> &Deq<String> {
         &self.string_table
@@ -60,6 +149,8 @@ impl Vm {
         self.stack_ptr = 0;
         self.stack.clear();
         self.instr_ptr = 0;
+        self.libraries.clear();
+        self.debug_info = <_>::default();
     }
 
     pub fn init<Args>(&mut self, revargs: Args) -> Result<()>
@@ -76,7 +167,7 @@ impl Vm {
         // - Cwd
         // - Args + argn
         let argc = revargs.len();
-        self.allocate(6 + argc);
+        self.allocate(CALL_CONTEXT_SIZE + argc);
         te!(self.push_null()); // retval allocation
         te!(self.push_val(0)); // # env var settings
         te!(self.push_val(0)); // # input redirections
@@ -115,22 +206,14 @@ impl Vm {
     }
 
     pub fn arg_addr(&self, argn: usize) -> Result<usize> {
-        // -n for stack-frame info: retaddr etc
-        // -1 because fp points 1 beyond last
         let &Self { frame_ptr, .. } = self;
-        let offset = 1 + self.call_stack_data().len() + argn;
-        if offset > frame_ptr {
-            temg!(
-                "Arg offset too big: fp:{fp} - 1 - stack_data:{sd} - argn:{an}",
-                fp = frame_ptr,
-                sd = self.call_stack_data().len(),
-                an = argn
-            )
-        }
-        Ok(frame_ptr - offset)
+        Ok(te!(
+            frame_arg_addr(frame_ptr, argn),
+            "Arg offset too big: fp:{frame_ptr} - 1 - stack_data:{CALL_STACK_DATA_SIZE} - argn:{argn}"
+        ))
     }
 
-    fn call_stack_data(&self) -> [usize; 2] {
+    fn call_stack_data(&self) -> [usize; CALL_STACK_DATA_SIZE] {
         [self.frame_ptr, self.instr_ptr]
     }
     pub fn ret_instr_addr(&self) -> Result<usize> {
@@ -161,6 +244,80 @@ impl Vm {
         vm.frame_ptr = vm.stack_ptr;
         Ok(())
     }
+    /// The call frames, innermost first, found by following the saved
+    /// frame pointers and return instructions. Stops at the frame set up by
+    /// [Self::init].
+    pub fn backtrace(&self) -> Vec<Frame> {
+        let vm = self;
+
+        let mut frames = vec![];
+        let mut fp = vm.frame_ptr;
+        let mut instr = vm.instr_ptr.wrapping_sub(1);
+        while fp >= 2 {
+            frames.push(Frame {
+                func_addr: vm.frame_func_addr(fp),
+                instr,
+            });
+            let (Ok(&ret_fp), Ok(&ret_instr)) =
+                (vm.stack_get::<usize>(fp - 2), vm.stack_get::<usize>(fp - 1))
+            else {
+                break;
+            };
+            if ret_instr == usize::MAX {
+                break;
+            }
+            fp = ret_fp;
+            instr = ret_instr - 1;
+        }
+        frames
+    }
+    /// Where the running syscall was called from: the innermost def, or the
+    /// top level, and the source location of the call, if known.
+    pub fn call_site(&self) -> String {
+        let frames = self.backtrace();
+        let def = frames
+            .iter()
+            .find_map(|frame| frame.func_addr)
+            .and_then(|addr| self.def_name(addr))
+            .unwrap_or("<top level>");
+        match frames
+            .first()
+            .and_then(|frame| self.debug_info.locate(frame.instr))
+        {
+            Some(location) => format!("{def} at {location}"),
+            None => def.to_owned(),
+        }
+    }
+    /// The name of the def at `addr`, unless it is the top level of a script
+    /// or not known.
+    fn def_name(&self, addr: usize) -> Option<&str> {
+        let def = self.debug_info.def_name(addr)?;
+        (def != SYSTEM_MAIN).then_some(def)
+    }
+    /// Tell the def at `addr` was called at `origin` to the listeners of job
+    /// events.
+    pub fn called(&self, addr: usize, origin: Option<&str>) {
+        if !events::listening() {
+            return;
+        }
+        if let Some(def) = self.def_name(addr) {
+            events::emit(Event::Call { def, origin });
+        }
+    }
+    /// The call target of the frame at `fp`, if it is a def.
+    fn frame_func_addr(&self, fp: usize) -> Option<usize> {
+        let &nargs: &usize = self.stack_get(frame_arg_addr(fp, 0)?).ok()?;
+        let target = self.stack_get_val(frame_arg_addr(fp, nargs + 2)?).ok()?;
+        match target {
+            &Value::FuncAddr(value::FuncAddr(addr)) => Some(addr),
+            _ => None,
+        }
+    }
+    /// Take the command of the last job that failed to clean up, if it
+    /// has not been taken yet.
+    pub fn take_failed_command(&mut self) -> Option<CommandLine> {
+        self.failed_command.take()
+    }
     pub fn nargs(&self) -> Result<usize> {
         let vm = self;
 
@@ -235,9 +392,18 @@ impl Vm {
         let ret_instr = te!(vm.ret_instr_addr());
         let ret_fp = vm.ret_fp_addr();
         ltrace!("return fp[{}] inst[{}]", ret_fp, ret_instr);
+        if events::listening() {
+            let def = vm.frame_func_addr(vm.frame_ptr);
+            if let Some(def) = def.and_then(|addr| vm.def_name(addr)) {
+                events::emit(Event::Return { def });
+            }
+        }
 
         vm.dealloc(vm.call_stack_data().len() + frame_size);
         vm.frame_ptr = ret_fp;
+        if vm.allocations >= vm.survivors.max(GC_MIN_ALLOCATIONS) {
+            vm.collect_garbage();
+        }
 
         vm.jump(ret_instr);
         Ok(())
@@ -374,49 +540,65 @@ impl Vm {
         );
     }
 
-    pub fn push_script(&mut self, icode: &ICode) -> Result<()> {
-        use syscall::util::{FramePtr, ValuesVmExt};
-
-        log::warn!("Not pushing ICODE");
-
-        // // Push icode as bytes on stack
-        // let FramePtr(x) = te!(self.add_tmp_icode(icode));
-        // // Translate frame_ptr to global address
-        // let x = self.frame_addr(x);
-        // ltrace!("Store icode at {x}");
-        // // Push
-        // self.scripts_stack.push(x);
-
-        Ok(())
-    }
-    pub fn current_script_value(&self) -> Result<&Value> {
-        // Find last script stack-ptr value on the script stack
-        let last_script_sp = *te!(self.scripts_stack.last(), "No script on the stack");
-        let value = te!(self.stack_get_val(last_script_sp));
-        Ok(value)
+    /// Make `object` the compiled object of the script about to run, as
+    /// seen by `__builtin __lib`.
+    pub fn push_script(&mut self, object: ScriptObject) {
+        self.scripts_stack.push(object);
     }
-    pub fn current_script_data(&self) -> Result<&[u8]> {
-        let jobid = te!(self.current_script_value());
-        let bytes: &[u8] = te!(self.val_as_bytes(jobid));
-        Ok(bytes)
+    /// The compiled object of the running script, written now if it was
+    /// not yet.
+    pub fn current_script_data(&mut self) -> Result<&[u8]> {
+        let script = te!(self.scripts_stack.last_mut(), "No script on the stack");
+        if let ScriptObject::Lazy(_) = script {
+            let ScriptObject::Lazy(write) = mem::replace(script, ScriptObject::Bytes(vec![]))
+            else {
+                unreachable!()
+            };
+            ltrace!("Write script object");
+            *script = ScriptObject::Bytes(te!(write()));
+        }
+        match script {
+            ScriptObject::Bytes(object) => Ok(object),
+            ScriptObject::Lazy(_) => unreachable!(),
+        }
     }
     pub fn pop_script(&mut self) {
-        // Do not ckean up. Assumingly the stack frame management will
-        // get to that eventually.
         self.scripts_stack.pop();
     }
 
     /// # Super public entrypoint
     pub fn eval_icode(&mut self, icode: &ICode) -> Result<()> {
-        self.load_icode(&icode)
-            .and_then(|_| self.run_instructions(icode))
+        let object = te!(icode_object(icode));
+        self.eval_object(icode, ScriptObject::Bytes(object))
+    }
+
+    /// Like [Self::eval_icode], for when a compiled object of the script
+    /// exists beyond its `icode` (for example, as produced by the
+    /// compiler). The script gets that `object` from `__builtin __lib`.
+    pub fn eval_object(&mut self, icode: &ICode, object: ScriptObject) -> Result<()> {
+        te!(self.load_object(icode, object));
+        te!(self.run_instructions(icode));
+        self.pop_script();
+        Ok(())
     }
 
     pub fn debug_icode(&mut self, icode: &ICode, bugger: Debugger) -> Result<Debugger> {
+        let object = te!(icode_object(icode));
+        self.debug_object(icode, ScriptObject::Bytes(object), bugger)
+    }
+
+    /// Like [Self::debug_icode], with the compiled `object` of the script
+    /// (see [Self::eval_object]).
+    pub fn debug_object(
+        &mut self,
+        icode: &ICode,
+        object: ScriptObject,
+        bugger: Debugger,
+    ) -> Result<Debugger> {
         let vm = self;
 
         vm.debugger = Some(bugger);
-        vm.load_icode(&icode)
+        vm.load_object(icode, object)
             .and_then(|_| vm.run_instructions(icode))
             .map(|_| vm.debugger.take().unwrap())
     }
@@ -436,10 +618,17 @@ impl Vm {
     }
 
     pub fn load_icode(&mut self, icode: &ICode) -> Result<()> {
-        te!(self.push_script(icode));
+        self.load_object(icode, ScriptObject::Bytes(te!(icode_object(icode))))
+    }
+
+    pub fn load_object(&mut self, icode: &ICode, object: ScriptObject) -> Result<()> {
+        if !self.no_verify {
+            te!(verify::verify(icode));
+        }
+        self.push_script(object);
         for (s, i) in &icode.strings {
             ltrace!("Load literal string {} {}", i.id, s);
-            self.add_string(i.clone(), s.clone());
+            self.add_string(*i, s.clone());
         }
         Ok(())
     }
@@ -447,17 +636,154 @@ impl Vm {
     pub fn run_instructions(&mut self, icode: &ICode) -> Result<()> {
         let vm = self;
 
-        while vm.instr_ptr < icode.instructions.len() {
-            let instruction = &icode.instructions[vm.instr_ptr];
+        while let Some(instruction) = vm.fetch(icode) {
+            let instruction = &instruction;
             te!(vm.wait_debugger(instruction));
+            let addr = vm.instr_ptr;
             vm.instr_ptr += 1;
             let success = instruction.operate_on(vm);
-            te!(success);
+            let location = match success {
+                Err(_) if addr < icode.instructions.len() => vm.debug_info.locate(addr),
+                _ => None,
+            };
+            match location {
+                Some(location) => te!(success, "at {}", location),
+                None => te!(success),
+            }
         }
         ltrace!("run_instructions: done");
         Ok(())
     }
 
+    /// The instruction at instr_ptr, from the script or from a loaded
+    /// library.
+    fn fetch(&self, icode: &ICode) -> Option<Instr> {
+        let addr = self.instr_ptr;
+        match icode.instructions.get(addr) {
+            Some(&instr) => Some(instr),
+            None => self.library_instr(addr),
+        }
+    }
+
+    fn library_instr(&self, addr: usize) -> Option<Instr> {
+        let lib = self.libraries.iter().find(|lib| lib.contains(addr))?;
+        lib.icode.instructions.get(addr - lib.base).copied()
+    }
+
+    pub fn set_debug_info(&mut self, debug_info: DebugInfo) {
+        self.debug_info = debug_info;
+    }
+
+    pub fn set_library_loader(&mut self, loader: library::Loader) {
+        self.library_loader = Some(loader);
+    }
+
+    /// Whether to verify code before running it, as by default.
+    pub fn set_verify(&mut self, verify: bool) {
+        self.no_verify = !verify;
+    }
+
+    /// Whether to replace invalid UTF-8 in outputs used as arguments (see
+    /// [Self::job_string]).
+    pub fn set_lossy_utf8(&mut self, lossy: bool) {
+        self.lossy_utf8 = lossy;
+    }
+
+    /// Whether to print commands instead of spawning them (see [DryRun]).
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run.then(DryRun::default);
+    }
+
+    pub fn dry_run(&mut self) -> Option<&mut DryRun> {
+        self.dry_run.as_mut()
+    }
+
+    /// Load, relocate and register the library at `path`, returning its
+    /// handle.
+    pub fn load_library(&mut self, path: &str) -> Result<usize> {
+        let vm = self;
+
+        let loader = te!(vm.library_loader, "No library loader set up");
+        let mut lib = te!(loader(path), "Loading library {path}");
+        if !vm.no_verify {
+            te!(verify::verify(&lib.icode), "Verifying library {path}");
+        }
+
+        lib.base = vm.libraries.last().map_or(library::BASE, Library::end);
+        let str_base = vm.string_table.len();
+        lib.icode.relocate(lib.base, str_base);
+        for addr in lib.defs.values_mut() {
+            *addr += lib.base;
+        }
+        for (s, i) in &lib.icode.strings {
+            ltrace!("Load library string {} {}", i.id, s);
+            vm.add_string(*i, s.clone());
+        }
+
+        ltrace!("Loaded library {} at {}", lib.path, lib.base);
+        vm.libraries.push(lib);
+        Ok(vm.libraries.len() - 1)
+    }
+
+    pub fn library(&self, handle: usize) -> Result<&Library> {
+        Ok(te!(
+            self.libraries.get(handle),
+            "No library with handle {handle}"
+        ))
+    }
+
+    /// Call def `name` of a loaded library, in a fresh call context on top
+    /// of the stack, and run it to completion.
+    ///
+    /// Returns the def's return value.
+    pub fn call_library(
+        &mut self,
+        handle: usize,
+        name: &str,
+        cwd: Value,
+        args: Vec<Value>,
+    ) -> Result<Value> {
+        let vm = self;
+
+        let lib = te!(vm.library(handle));
+        let addr = *te!(lib.defs.get(name), "No def {name} in library {}", lib.path);
+
+        let context_size = CALL_CONTEXT_SIZE + args.len();
+        let retval_addr = vm.stack_ptr;
+        vm.allocate(context_size);
+        te!(vm.push_null()); // retval allocation
+        te!(vm.push_val(0)); // # env var settings
+        te!(vm.push_val(0)); // # input redirections
+        te!(vm.push_val(value::FuncAddr(addr))); // invocation target
+        te!(vm.push_val(cwd));
+        let nargs = args.len();
+        for arg in args.into_iter().rev() {
+            te!(vm.push_val(arg));
+        }
+        te!(vm.push_val(nargs));
+
+        // Return to an address outside of everything, like [Self::init]
+        let ret_instr = mem::replace(&mut vm.instr_ptr, usize::MAX);
+        te!(vm.prepare_call());
+        vm.jump(addr);
+        vm.called(addr, None);
+        // Library code is fetched past the script code, which it never
+        // calls into, so no script code is needed to run it
+        te!(vm.run_instructions(&ICode::default()));
+        if vm.instr_ptr != usize::MAX {
+            temg!(
+                "Instruction address outside of library {}: {}",
+                vm.libraries[handle].path,
+                vm.instr_ptr
+            )
+        }
+        vm.instr_ptr = ret_instr;
+
+        let retval = mem::take(te!(vm.stack_get_val_mut(retval_addr)));
+        vm.dealloc(context_size);
+        Ok(retval)
+    }
+
     /// Set the next instr_ptr to be executed
     pub fn jump(&mut self, addr: usize) {
         ltrace!("[jump] {}", addr);
@@ -470,6 +796,7 @@ impl Vm {
     {
         let vm = self;
         vm.bin_path = source.as_ref().split(':').map(<_>::to_owned).collect();
+        vm.bin_cache.clear();
     }
     pub fn init_bin_path_system(&mut self) {
         self.init_bin_path("/sbin:/usr/sbin:/usr/local/sbin:/bin:/usr/bin:/usr/local/bin")
@@ -484,6 +811,85 @@ impl Vm {
         self.init_bin_path_from_env("PATH")
     }
 
+    /// The program to run for the command `name`: the first executable file
+    /// of that name in the directories of `path`, the PATH the invocation
+    /// sets, if any, or else of [Self::bin_path], unless `name` is a path
+    /// already. Relative directories are relative to `cwd`, the working
+    /// directory of the invocation, if it sets one.
+    ///
+    /// Programs found in [Self::bin_path] are kept for later invocations,
+    /// unless after a relative directory, of which the contents depend on
+    /// the working directory.
+    pub fn find_command(
+        &mut self,
+        name: &str,
+        path: Option<&str>,
+        cwd: Option<&Path>,
+    ) -> Result<PathBuf> {
+        if name.contains('/') {
+            return Ok(name.into());
+        }
+        if let (None, Some(program)) = (path, self.bin_cache.get(name)) {
+            return Ok(program.to_owned());
+        }
+
+        let dirs: Vec<&str> = match path {
+            Some(path) => path.split(':').collect(),
+            None => self.bin_path.iter().map(String::as_str).collect(),
+        };
+        let found = dirs.iter().enumerate().find_map(|(i, dir)| {
+            // An empty directory is the current one, as in PATH
+            let dir = Path::new(if dir.is_empty() { "." } else { dir });
+            let program = match cwd {
+                Some(cwd) if dir.is_relative() => cwd.join(dir).join(name),
+                _ => dir.join(name),
+            };
+            let meta = program.metadata().ok()?;
+            (meta.is_file() && meta.permissions().mode() & 0o111 != 0).then_some((i, program))
+        });
+        let Some((i, program)) = found else {
+            temg!("command not found: {name} (searched: {})", dirs.join(":"))
+        };
+        // Not to be taken as relative to the working directory of the
+        // invocation, once there
+        let program = te!(std::path::absolute(program));
+        ltrace!("[find_command] {name} = {program:?}");
+        self.bin_names.insert(program.to_owned(), name.to_owned());
+        let relative = |dir: &&str| dir.is_empty() || Path::new(dir).is_relative();
+        if path.is_none() && !dirs[..=i].iter().any(relative) {
+            self.bin_cache.insert(name.to_owned(), program.to_owned());
+        }
+        Ok(program)
+    }
+
+    /// `line` with its program named as invoked, rather than as found.
+    fn invoked(&self, mut line: CommandLine) -> CommandLine {
+        if let Some(name) = self.bin_names.get(Path::new(&line.program)) {
+            line.program = name.to_owned();
+        }
+        line
+    }
+
+    /// Take the settings of all jobs from [TEE_ENV], [TIMEOUT_ENV] and
+    /// [TRACE_ENV].
+    pub fn init_settings_from_env(&mut self) -> Result<()> {
+        let env = |name| std::env::var_os(name).unwrap_or_default();
+        self.tee = te!(parse_setting(TEE_ENV, &env(TEE_ENV)));
+        self.timeout = te!(parse_setting(TIMEOUT_ENV, &env(TIMEOUT_ENV)));
+        if let Some(trace) = te!(parse_setting::<job::Trace>(TRACE_ENV, &env(TRACE_ENV))) {
+            te!(trace.start());
+        }
+        Ok(())
+    }
+
+    pub fn tee(&self) -> Option<&job::Tee> {
+        self.tee.as_ref()
+    }
+
+    pub fn timeout(&self) -> Option<job::Timeout> {
+        self.timeout
+    }
+
     pub fn write_to<O>(&self, o: io::Result<O>) -> Result<()>
     where
         O: io::Write,
@@ -726,7 +1132,16 @@ impl Vm {
         let vm = self;
 
         match val {
-            Value::Job(value::Job(proc_id)) => te!(cln(te!(vm.get_job_mut(proc_id)))),
+            Value::Job(value::Job(proc_id)) => {
+                let job = te!(vm.get_job_mut(proc_id));
+                let command = job.command().map(CommandLine::from);
+                let result = cln(job);
+                vm.failed_command = match &result {
+                    Ok(_) => None,
+                    Err(_) => command.map(|command| vm.invoked(command)),
+                };
+                te!(result)
+            }
             v @ (Value::ArrayView(_)
             | Value::FuncAddr(_)
             | Value::LitString(_)
@@ -775,13 +1190,38 @@ impl Vm {
     where
         C: Into<Job>,
     {
-        let Self { job_table: t, .. } = self;
-        let id = t.len();
+        let Self {
+            job_table: t,
+            free_jobs: free,
+            ..
+        } = self;
         let job = child.into();
+        self.allocations += 1;
 
-        t.push_back(job);
+        match free.pop() {
+            Some(id) => {
+                t[id] = job;
+                id
+            }
+            None => {
+                t.push_back(job);
+                t.len() - 1
+            }
+        }
+    }
 
-        id
+    /// The output of job `jobid` as text, to use as an argument.
+    ///
+    /// Outputs are kept as bytes until then, so that binary ones can still
+    /// be used as inputs.
+    pub fn job_string(&mut self, jobid: usize) -> Result<Cow<'_, str>> {
+        let lossy = self.lossy_utf8;
+        let job = te!(self.get_job_mut(jobid));
+        Ok(if lossy {
+            te!(job.make_string_lossy())
+        } else {
+            Cow::Borrowed(te!(job.make_string()))
+        })
     }
 
     pub fn get_job_mut(&mut self, jobid: usize) -> Result<&mut Job> {
@@ -796,10 +1236,83 @@ impl Vm {
 
     pub fn add_dynstring(&mut self, s: String) -> usize {
         let Self {
-            dynstring_table: d, ..
+            dynstring_table: d,
+            free_dynstrings: free,
+            ..
         } = self;
-        d.push_back(s);
-        d.len() - 1
+        self.allocations += 1;
+
+        match free.pop() {
+            Some(id) => {
+                d[id] = s;
+                id
+            }
+            None => {
+                d.push_back(s);
+                d.len() - 1
+            }
+        }
+    }
+
+    /// Reclaim the jobs and dynstrings that no stack cell refers to. Their
+    /// ids are reused for new ones. Running jobs are left for their clean-up to
+    /// wait for, but not hubs, which wait for their job themselves; finished
+    /// ones are cleaned up.
+    pub fn collect_garbage(&mut self) {
+        let vm = self;
+
+        let mut jobs = vec![false; vm.job_table.len()];
+        let mut dynstrings = vec![false; vm.dynstring_table.len()];
+        for &id in &vm.free_jobs {
+            jobs[id] = true;
+        }
+        for &id in &vm.free_dynstrings {
+            dynstrings[id] = true;
+        }
+        for val in &vm.stack {
+            let (marks, id) = match *val {
+                Value::Job(value::Job(id)) => (&mut jobs, id),
+                Value::DynString(value::DynString(id)) => (&mut dynstrings, id),
+                _ => continue,
+            };
+            if let Some(mark) = marks.get_mut(id) {
+                *mark = true;
+            }
+        }
+
+        for (id, job) in vm.job_table.iter_mut().enumerate() {
+            match job {
+                _ if jobs[id] => continue,
+                Job::System(sys) if !sys.finished() => continue,
+                Job::System(_) => {
+                    // Nothing is left to tell a failure to, as its output
+                    // can no longer be read
+                    if let Err(err) = job.cleanup() {
+                        ltrace!("collect garbage: job {}: {:?}", id, err);
+                    }
+                }
+                _ => *job = Job::default(),
+            }
+            vm.free_jobs.push(id);
+        }
+        for (id, s) in vm.dynstring_table.iter_mut().enumerate() {
+            if !dynstrings[id] {
+                *s = String::new();
+                vm.free_dynstrings.push(id);
+            }
+        }
+
+        vm.allocations = 0;
+        vm.survivors = vm.job_table.len() - vm.free_jobs.len() + vm.dynstring_table.len()
+            - vm.free_dynstrings.len();
+        ltrace!(
+            "collect garbage: {} survivors, {} of {} jobs free, {} of {} dynstrings free",
+            vm.survivors,
+            vm.free_jobs.len(),
+            vm.job_table.len(),
+            vm.free_dynstrings.len(),
+            vm.dynstring_table.len()
+        );
     }
 
     pub fn get_dynstring_id(&self, id: usize) -> Result<&str> {
@@ -809,3 +1322,30 @@ impl Vm {
         Ok(te!(d.get(id)))
     }
 }
+
+/// The address of arg `argn` of the frame at `fp`: the args are below the
+/// call stack data, and `fp` points one past it.
+fn frame_arg_addr(fp: usize, argn: usize) -> Option<usize> {
+    fp.checked_sub(1 + CALL_STACK_DATA_SIZE + argn)
+}
+
+/// The bare serialized `icode`, as an object of a script that has nothing
+/// more.
+fn icode_object(icode: &ICode) -> Result<Vec<u8>> {
+    let mut object = Vec::new();
+    te!(icode.write_to(Ok(&mut object)));
+    Ok(object)
+}
+
+/// Parse `setting`, the value of the environment variable `name`, where an
+/// empty value sets nothing.
+pub fn parse_setting<T>(name: &str, setting: &OsStr) -> Result<Option<T>>
+where
+    T: FromStr<Err = job::Error>,
+{
+    if setting.is_empty() {
+        return Ok(None);
+    }
+    let setting = te!(setting.to_str(), "{name} = {setting:?}");
+    Ok(Some(te!(setting.parse::<T>(), "{name} = {setting:?}")))
+}